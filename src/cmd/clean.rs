@@ -13,15 +13,18 @@
 // limitations under the License.
 
 use std::fmt::Display;
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use amp_client::client::Client;
 use clap::Args;
 use inquire::Select;
-use tracing::info;
+use tracing::{info, warn};
 
 use crate::context::Context;
 use crate::errors::{Errors, Result};
+use crate::ops::pipeline;
+use crate::state::State;
 
 /// Delete any resources deployed by Amphitheatre
 #[derive(Args, Debug)]
@@ -45,11 +48,26 @@ pub struct Cli {
 
 impl Cli {
     pub async fn exec(&self, ctx: Arc<Context>) -> Result<()> {
+        let leftover = leftover();
+
         if let Some(id) = &self.id {
-            return delete(&ctx.client, id).await;
+            return delete(&ctx.client, id, &leftover).await;
+        }
+
+        let mut playbooks = ctx.client.playbooks().list(None).await.map_err(Errors::ClientError)?;
+
+        // Tell the user about the playbook left behind by a dev session in the current workspace.
+        if let Some((workspace, state)) = &leftover {
+            if let Some(index) = playbooks.iter().position(|p| p.id == state.playbook) {
+                warn!("Found playbook #{} left behind by a dev session in {}", state.playbook, workspace.display());
+                let playbook = playbooks.remove(index);
+                playbooks.insert(0, playbook);
+            } else {
+                // The playbook was deleted already, the state is stale.
+                State::remove(workspace)?;
+            }
         }
 
-        let playbooks = ctx.client.playbooks().list(None).await.map_err(Errors::ClientError)?;
         if playbooks.is_empty() {
             println!("No playbooks found");
             return Ok(());
@@ -62,16 +80,24 @@ impl Cli {
             }
 
             for playbook in playbooks {
-                delete(&ctx.client, &playbook.id).await?;
+                delete(&ctx.client, &playbook.id, &leftover).await?;
             }
 
             return Ok(());
         }
 
         // create a options list for the user to select from
-        let options: Vec<OptionItem> = playbooks.iter().map(|p| OptionItem(p.id.clone(), p.title.clone())).collect();
+        let options: Vec<OptionItem> = playbooks
+            .iter()
+            .map(|p| match &leftover {
+                Some((_, state)) if state.playbook == p.id => {
+                    OptionItem(p.id.clone(), format!("{} (left behind by a dev session)", p.title))
+                }
+                _ => OptionItem(p.id.clone(), p.title.clone()),
+            })
+            .collect();
         let answer = Select::new("Select playbook to delete: ", options).prompt().map_err(Errors::InquireError)?;
-        delete(&ctx.client, answer.0.as_str()).await?;
+        delete(&ctx.client, answer.0.as_str(), &leftover).await?;
 
         Ok(())
     }
@@ -86,7 +112,16 @@ impl Display for OptionItem {
     }
 }
 
-async fn delete(client: &Client, id: &str) -> Result<()> {
+/// Find the session state left behind in the current workspace, if any.
+fn leftover() -> Option<(PathBuf, State)> {
+    let path = pipeline::locate(&None).ok()?;
+    let workspace = path.parent()?.to_path_buf();
+    let state = State::load(&workspace).ok()??;
+
+    Some((workspace, state))
+}
+
+async fn delete(client: &Client, id: &str, leftover: &Option<(PathBuf, State)>) -> Result<()> {
     let status = client.playbooks().delete(id).await.map_err(Errors::ClientError)?;
     if status != 204 {
         return Err(Errors::FailedDeletePlaybook(id.to_string()));
@@ -94,5 +129,12 @@ async fn delete(client: &Client, id: &str) -> Result<()> {
 
     info!("Deleted playbook {}", id);
 
+    // Forget the dev session if its playbook was deleted.
+    if let Some((workspace, state)) = leftover {
+        if state.playbook == id {
+            State::remove(workspace)?;
+        }
+    }
+
     Ok(())
 }
@@ -46,6 +46,10 @@ pub struct Cli {
     #[arg(long, action = clap::ArgAction::SetTrue, env = "AMP_INTERACTIVE", global=true)]
     interactive: bool,
 
+    /// Fail on undefined variables in the manifest instead of replacing them with empty strings
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "AMP_STRICT_VARS", global=true)]
+    strict_vars: bool,
+
     /// Print timestamps in logs
     #[arg(long, action = clap::ArgAction::SetTrue, env = "AMP_TIMESTAMPS", global=true)]
     timestamps: bool,
@@ -69,35 +73,61 @@ enum Commands {
     Deploy(super::deploy::Cli),
     Dev(super::dev::Cli),
     Diagnose(super::diagnose::Cli),
+    Diff(super::diff::Cli),
+    Exec(super::exec::Cli),
+    Fmt(super::fmt::Cli),
     Init(super::init::Cli),
     List(super::list::Cli),
+    Manifest(super::manifest::cli::Cli),
     Options(super::options::Cli),
+    PortForward(super::port_forward::Cli),
     Render(super::render::Cli),
     Run(super::run::Cli),
+    Schema(super::schema::Cli),
+    Sync(super::sync::Cli),
     Test(super::test::Cli),
+    Validate(super::validate::Cli),
     Version(super::version::Cli),
 }
 
 impl Cli {
-    pub async fn exec(&self, ctx: Arc<Context>) -> Result<()> {
+    pub async fn exec(&self) -> Result<()> {
+        // The context is only loaded for the commands talking to the server,
+        // so the offline commands work without a valid configuration.
         match &self.command {
-            Commands::Clean(cli) => cli.exec(ctx).await,
-            Commands::Context(cli) => cli.exec(ctx).await,
+            Commands::Clean(cli) => cli.exec(self.context().await?).await,
+            Commands::Context(cli) => cli.exec(self.context().await?).await,
             Commands::Completion(cli) => cli.exec(),
-            Commands::Config(cli) => cli.exec(ctx).await,
-            Commands::Debug(cli) => cli.exec(ctx).await,
-            Commands::Deploy(cli) => cli.exec(ctx).await,
-            Commands::Dev(cli) => cli.exec(ctx).await,
-            Commands::Diagnose(cli) => cli.exec(ctx).await,
-            Commands::Init(cli) => cli.exec(ctx).await,
-            Commands::List(cli) => cli.exec(ctx).await,
+            Commands::Config(cli) => cli.exec(self.context().await?).await,
+            Commands::Debug(cli) => cli.exec(self.context().await?).await,
+            Commands::Deploy(cli) => cli.exec(self.context().await?).await,
+            Commands::Dev(cli) => cli.exec(self.context().await?).await,
+            Commands::Diagnose(cli) => cli.exec(self.context().await.ok()).await,
+            Commands::Diff(cli) => cli.exec(self.context().await?).await,
+            Commands::Exec(cli) => cli.exec(self.context().await?).await,
+            Commands::Fmt(cli) => cli.exec(),
+            Commands::Init(cli) => cli.exec(self.interactive).await,
+            Commands::List(cli) => cli.exec(self.context().await?).await,
+            Commands::Manifest(cli) => cli.exec(),
             Commands::Options(cli) => cli.exec(),
-            Commands::Render(cli) => cli.exec(ctx).await,
-            Commands::Run(cli) => cli.exec(ctx).await,
-            Commands::Test(cli) => cli.exec(ctx).await,
+            Commands::PortForward(cli) => cli.exec(self.context().await?).await,
+            Commands::Render(cli) => cli.exec(self.strict_vars).await,
+            Commands::Run(cli) => cli.exec(self.context().await?).await,
+            Commands::Schema(cli) => cli.exec(),
+            Commands::Sync(cli) => cli.exec(),
+            Commands::Test(cli) => cli.exec(self.context().await?).await,
+            Commands::Validate(cli) => cli.exec(),
             Commands::Version(cli) => cli.exec(),
         }
     }
+
+    /// Load the context from the configuration, with the global options applied.
+    async fn context(&self) -> Result<Arc<Context>> {
+        let ctx = Context::init()?;
+        *ctx.session.strict.write().await = self.strict_vars;
+
+        Ok(Arc::new(ctx))
+    }
 }
 
 #[test]
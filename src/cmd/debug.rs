@@ -12,69 +12,142 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use clap::Args;
+use inquire::Confirm;
+use tracing::{info, warn};
 
 use crate::context::Context;
-use crate::errors::Result;
+use crate::errors::{Errors, Result};
+use crate::ops::debugger::{self, Protocol};
+use crate::ops::forwarder::Mapping;
+use crate::ops::pipeline::Options;
+use crate::ops::watcher::Trigger;
+use crate::ops::{cleaner, pipeline, watcher};
+use crate::utils;
 
 /// Run a pipeline in debug mode
 #[derive(Args, Debug)]
 #[command(after_help = super::cli::AFTER_HELP_STRING)]
 pub struct Cli {
-    /// If true, amp will skip yes/no confirmation from the user
-    #[arg(long, action = clap::ArgAction::Set, default_value = "true", env = "AMP_ASSUME_YES")]
-    assume_yes: bool,
-
-    /// When set to false, builds wait for API request instead of running automatically
-    #[arg(long, action = clap::ArgAction::SetTrue, env = "AMP_AUTO_BUILD")]
+    /// If true, amp will skip yes/no confirmation from the user,
+    /// defaults to true only when the input is not a terminal
+    #[arg(long, action = clap::ArgAction::Set, env = "AMP_ASSUME_YES")]
+    assume_yes: Option<bool>,
+
+    /// When set to false, asks before building, declining deploys the image configured
+    /// in the manifest without building the local sources
+    #[arg(long, action = clap::ArgAction::Set, default_value = "true", env = "AMP_AUTO_BUILD")]
     auto_build: bool,
 
-    /// If true, amp will try to create a config for the user's run if it doesn't find one
-    #[arg(long, action = clap::ArgAction::SetTrue, env = "AMP_AUTO_CREATE_CONFIG")]
-    auto_create_config: bool,
-
-    /// When set to false, deploys wait for API request instead of running automatically
-    #[arg(long, action = clap::ArgAction::SetTrue, env = "AMP_AUTO_DEPLOY")]
+    /// When set to false, asks before deploying, declining stops before anything is built,
+    /// as the character is built on the cluster as part of its deployment
+    #[arg(long, action = clap::ArgAction::Set, default_value = "true", env = "AMP_AUTO_DEPLOY")]
     auto_deploy: bool,
 
-    /// When set to false, syncs wait for API request instead of running automatically
-    #[arg(long, action = clap::ArgAction::SetTrue, env = "AMP_AUTO_SYNC")]
+    /// When set to false, syncs wait for `amp sync` instead of running automatically
+    #[arg(long, action = clap::ArgAction::Set, default_value = "true", env = "AMP_AUTO_SYNC")]
     auto_sync: bool,
 
     /// Delete deployments after dev or debug mode is interrupted
     #[arg(long, action = clap::ArgAction::SetTrue, env = "AMP_CLEANUP")]
     cleanup: bool,
 
+    /// The quiet period in milliseconds to wait for more changes before syncing them together
+    #[arg(long, value_name = "MS", default_value = "300", env = "AMP_DEBOUNCE")]
+    debounce: u64,
+
     /// Path or URL to the Amphitheatre config file
     #[arg(short, long, env = "AMP_FILENAME")]
-    filename: Option<String>,
-
-    /// Recreate Kubernetes resources if necessary for deployment,
-    /// warning: might cause downtime!
-    #[arg(long, action = clap::ArgAction::SetTrue, env = "AMP_FORCE")]
-    force: bool,
+    filename: Option<PathBuf>,
 
-    /// Activate profiles by name (prefixed with `-` to disable a profile)
-    #[arg(short, long, default_value = "[]", env = "AMP_PROFILE")]
-    profile: Option<Vec<String>>,
+    /// The interval in milliseconds to scan the workspace with the polling trigger
+    #[arg(long, value_name = "MS", default_value = "1000", env = "AMP_POLL_INTERVAL")]
+    poll_interval: u64,
 
-    /// Priority sorted order of debugger protocols to support
-    #[arg(long, default_value = "[]", env = "AMP_PROTOCOLS")]
-    protocols: Option<String>,
+    /// Priority sorted order of debugger protocols to support, dlv and debugpy are not
+    /// injected, the character must start under the debugger, e.g. with `dlv exec` or
+    /// `python -m debugpy`, listening on the default port of the protocol
+    #[arg(long, value_enum, value_delimiter = ',', env = "AMP_PROTOCOLS")]
+    protocols: Vec<Protocol>,
 
     /// Stream logs from deployed objects
     #[arg(long, action = clap::ArgAction::SetTrue, env = "AMP_TAIL")]
     tail: bool,
 
     /// How is change detection triggered? (polling, notify, or manual)
-    #[arg(long, default_value = "notify", env = "AMP_TRIGGER")]
-    trigger: Option<String>,
+    #[arg(long, value_enum, default_value = "notify", env = "AMP_TRIGGER")]
+    trigger: Trigger,
 }
 
 impl Cli {
-    pub async fn exec(&self, _ctx: Arc<Context>) -> Result<()> {
-        Ok(())
+    pub async fn exec(&self, ctx: Arc<Context>) -> Result<()> {
+        // Setup handler for for handling Ctrl-C signals.
+        cleaner::setup_signal_handler(ctx.clone(), self.cleanup);
+
+        // Load the character and choose the debugger protocol for its runtime.
+        pipeline::prepare(&ctx.session, &self.filename).await?;
+        let workspace = ctx.session.workspace.read().await.clone().unwrap();
+        let protocol = debugger::negotiate(&workspace, &self.protocols).ok_or_else(|| {
+            let tried = if self.protocols.is_empty() { &Protocol::ALL[..] } else { &self.protocols };
+            Errors::NotFoundDebuggerProtocol(tried.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(", "))
+        })?;
+        info!("Debugging with the {} protocol", protocol);
+
+        // Wait for the user before each step which is not automatic.
+        let assume_yes = utils::assume_yes(self.assume_yes);
+        if !self.auto_deploy && !confirm("Deploy the character?", assume_yes)? {
+            info!("The character is not deployed, run `amp debug` again when you are ready");
+            return Ok(());
+        }
+        let build = self.auto_build || confirm("Build the character from the local sources?", assume_yes)?;
+
+        // Start the actor in debug mode, or the configured image if the build is skipped.
+        let mut character = pipeline::spec(&ctx.session, false, &protocol.env()).await?;
+        if !build {
+            let image = character.deploy.as_ref().and_then(|deploy| deploy.image.clone());
+            let image = image.ok_or_else(|| Errors::NotFoundImage(character.meta.name.clone()))?;
+            info!("Skipping the build, deploying the image {}", image);
+            character.build = None;
+            character.live = false;
+        }
+        if let Some(launch) = protocol.launch() {
+            warn!("Make sure the character starts under the debugger, e.g. `{}`", launch);
+        }
+
+        // Define the options for the pipeline.
+        let port = protocol.port();
+        let opt = Options {
+            cleanup: self.cleanup,
+            tail: self.tail, // toggle log streaming
+            live: build,     // sync the sources from local to server
+            once: false,     // keep the actor running for debugging
+            sync: build,     // sync the changes, on demand without auto sync
+            forwards: vec![Mapping { local: port, remote: port }],
+            watch: watcher::Settings {
+                trigger: if self.auto_sync { self.trigger } else { Trigger::Manual },
+                debounce: Duration::from_millis(self.debounce),
+                poll_interval: Duration::from_millis(self.poll_interval),
+            },
+        };
+        let playbook = pipeline::submit(&ctx, &character).await?;
+
+        // Print the configuration for attaching the IDE to the forwarded port.
+        let attach = protocol.attach(&character.meta.name, port);
+        println!("The debugger will be available at 127.0.0.1:{}, attach your IDE with:", port);
+        println!("{}", serde_json::to_string_pretty(&attach).unwrap_or_default());
+
+        // Run debug mode, sync the sources and forward the debugger port.
+        pipeline::run(&ctx, playbook, opt).await
+    }
+}
+
+fn confirm(message: &str, assume_yes: bool) -> Result<bool> {
+    if assume_yes {
+        return Ok(true);
     }
+    Confirm::new(message).with_default(true).prompt().map_err(Errors::InquireError)
 }
@@ -12,36 +12,70 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
+use amp_client::playbooks::PlaybookPayload;
+use amp_common::resource::CharacterSpec;
 use clap::Args;
+use inquire::Confirm;
 
 use crate::context::Context;
-use crate::errors::Result;
+use crate::errors::{Errors, Result};
+use crate::format::Format;
+use crate::ops::pipeline::Options;
+use crate::ops::{artifacts, cleaner, env, pipeline};
+use crate::utils;
 
 /// Deploy pre-built artifacts
 #[derive(Args, Debug)]
 #[command(after_help = super::cli::AFTER_HELP_STRING)]
 pub struct Cli {
-    /// If true, amp will skip yes/no confirmation from the user
-    #[arg(long, action = clap::ArgAction::Set, default_value = "true", env = "AMP_ASSUME_YES")]
-    assume_yes: bool,
+    /// If true, amp will skip yes/no confirmation from the user,
+    /// defaults to true only when the input is not a terminal
+    #[arg(long, action = clap::ArgAction::Set, env = "AMP_ASSUME_YES")]
+    assume_yes: Option<bool>,
+
+    /// File containing the build artifacts in JSON format
+    #[arg(short = 'a', long, env = "AMP_BUILD_ARTIFACTS")]
+    build_artifacts: Option<PathBuf>,
+
+    /// Set environment variables for the character (KEY=VALUE), overrides the manifest
+    #[arg(short, long = "env", value_name = "KEY=VALUE", value_parser = env::parse_pair)]
+    env: Vec<(String, String)>,
+
+    /// Read environment variables from a file, overrides the manifest
+    #[arg(long, value_name = "FILE", env = "AMP_ENV_FILE")]
+    env_file: Vec<PathBuf>,
 
     /// Path or URL to the Amphitheatre config file
     #[arg(short, long, env = "AMP_FILENAME")]
-    filename: Option<String>,
+    filename: Option<PathBuf>,
 
     /// Recreate Kubernetes resources if necessary for deployment,
     /// warning: might cause downtime!
     #[arg(long, action = clap::ArgAction::SetTrue, env = "AMP_FORCE")]
     force: bool,
 
-    /// Activate profiles by name (prefixed with `-` to disable a profile)
-    #[arg(short, long, default_value = "[]", env = "AMP_PROFILE")]
-    profile: Option<Vec<String>>,
+    /// The pre-built images to deploy, in the form of NAME=IMAGE, or IMAGE for the lead character
+    #[arg(short, long, value_name = "NAME=IMAGE", value_parser = artifacts::parse_image, value_delimiter = ',')]
+    images: Vec<(String, String)>,
+
+    /// The ID of the playbook to update, defaults to the one titled with the character name
+    #[arg(long, env = "AMP_PLAYBOOK")]
+    playbook: Option<String>,
 
-    /// Don't render the manifests, just deploy them
-    #[arg(long, action = clap::ArgAction::SetTrue, env = "AMP_SKIP_RENDER")]
+    /// Deploy the payload rendered by `amp render` into the file given with `--filename`
+    /// as it is, instead of rendering the manifest
+    #[arg(
+        long,
+        action = clap::ArgAction::SetTrue,
+        requires = "filename",
+        conflicts_with_all = ["build_artifacts", "env", "env_file", "images"],
+        env = "AMP_SKIP_RENDER"
+    )]
     skip_render: bool,
 
     /// Stream logs from deployed objects
@@ -50,7 +84,61 @@ pub struct Cli {
 }
 
 impl Cli {
-    pub async fn exec(&self, _ctx: Arc<Context>) -> Result<()> {
-        Ok(())
+    pub async fn exec(&self, ctx: Arc<Context>) -> Result<()> {
+        // Setup handler for for handling Ctrl-C signals, the deployment is kept.
+        cleaner::setup_signal_handler(ctx.clone(), false);
+
+        let payload = match self.skip_render {
+            true => self.rendered()?,
+            false => pipeline::deployment(&self.render(&ctx).await?),
+        };
+
+        // Recreating the playbook might cause downtime, ask the user first.
+        let mut force = self.force;
+        if force && !utils::assume_yes(self.assume_yes) {
+            let message = "Recreate the existing playbook? It might cause downtime";
+            force = Confirm::new(message).with_default(false).prompt().map_err(Errors::InquireError)?;
+        }
+
+        let playbook = pipeline::deploy(&ctx, payload, &self.playbook, force).await?;
+
+        // Define the options for the pipeline.
+        let opt = Options {
+            cleanup: false,  // keep the deployment
+            tail: self.tail, // toggle log streaming
+            live: false,     // nothing to sync, the image is pre-built
+            once: true,      // deploy once, then exit
+            sync: false,
+            forwards: vec![],
+            watch: Default::default(),
+        };
+
+        pipeline::run(&ctx, playbook, opt).await
+    }
+
+    /// Load the character and replace the build stage with the pre-built image.
+    async fn render(&self, ctx: &Context) -> Result<CharacterSpec> {
+        // The images given on the command line take precedence over the build artifacts.
+        let mut images = HashMap::new();
+        if let Some(path) = &self.build_artifacts {
+            images.extend(artifacts::load(path)?);
+        }
+        images.extend(self.images.iter().cloned());
+
+        // Collect the environment variables injected from command line.
+        let vars = env::collect(&self.env_file, &self.env)?;
+
+        pipeline::prepare(&ctx.session, &self.filename).await?;
+        let mut character = pipeline::deployable(&ctx.session, &vars).await?;
+        artifacts::apply(&mut character, &images)?;
+
+        Ok(character)
+    }
+
+    /// Read the payload rendered by `amp render`, in the format of its extension.
+    fn rendered(&self) -> Result<PlaybookPayload> {
+        let path = self.filename.as_deref().unwrap_or(Path::new(""));
+        let content = fs::read_to_string(path).map_err(Errors::FailedReadManifest)?;
+        Format::from_path(path).deserialize(&content)
     }
 }
@@ -15,28 +15,53 @@
 use clap::Args;
 use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
 use crate::context::Context;
 use crate::errors::Result;
+use crate::ops::forwarder::Mapping;
 use crate::ops::pipeline::Options;
-use crate::ops::{cleaner, pipeline};
+use crate::ops::watcher::Trigger;
+use crate::ops::{cleaner, env, pipeline, watcher};
+use crate::utils;
 
 /// Run a pipeline in development mode
 #[derive(Args, Debug)]
 #[command(after_help = super::cli::AFTER_HELP_STRING)]
 pub struct Cli {
-    /// If true, amp will skip yes/no confirmation from the user
-    #[arg(long, action = clap::ArgAction::Set, default_value = "true", env = "AMP_ASSUME_YES")]
-    assume_yes: bool,
+    /// If true, amp will skip yes/no confirmation from the user,
+    /// defaults to true only when the input is not a terminal
+    #[arg(long, action = clap::ArgAction::Set, env = "AMP_ASSUME_YES")]
+    assume_yes: Option<bool>,
 
     /// Delete deployments after dev or debug mode is interrupted
     #[arg(long, action = clap::ArgAction::Set, default_value = "true", env = "AMP_CLEANUP")]
     cleanup: bool,
 
+    /// The quiet period in milliseconds to wait for more changes before syncing them together
+    #[arg(long, value_name = "MS", default_value = "300", env = "AMP_DEBOUNCE")]
+    debounce: u64,
+
+    /// Set environment variables for the character (KEY=VALUE), overrides the manifest
+    #[arg(short, long = "env", value_name = "KEY=VALUE", value_parser = env::parse_pair)]
+    env: Vec<(String, String)>,
+
+    /// Read environment variables from a file, overrides the manifest
+    #[arg(long, value_name = "FILE", env = "AMP_ENV_FILE")]
+    env_file: Vec<PathBuf>,
+
+    /// Forward local ports to the lead character, in the form of LOCAL:REMOTE or PORT
+    #[arg(long, value_name = "LOCAL:REMOTE", env = "AMP_FORWARD")]
+    forward: Vec<Mapping>,
+
     /// Path or URL to the Amphitheatre config file
     #[arg(short, long, env = "AMP_FILENAME")]
     filename: Option<PathBuf>,
 
+    /// The interval in milliseconds to scan the workspace with the polling trigger
+    #[arg(long, value_name = "MS", default_value = "1000", env = "AMP_POLL_INTERVAL")]
+    poll_interval: u64,
+
     /// Activate profiles by name (prefixed with `-` to disable a profile)
     #[arg(short, long, env = "AMP_PROFILE")]
     profile: Option<Vec<String>>,
@@ -46,8 +71,8 @@ pub struct Cli {
     tail: bool,
 
     /// How is change detection triggered? (polling, notify, or manual)
-    #[arg(long, default_value = "notify", env = "AMP_TRIGGER")]
-    trigger: Option<String>,
+    #[arg(long, value_enum, default_value = "notify", env = "AMP_TRIGGER")]
+    trigger: Trigger,
 }
 
 impl Cli {
@@ -61,8 +86,24 @@ impl Cli {
             tail: self.tail, // toggle log streaming
             live: true,      // sync the sources from local to server
             once: false,     // watch for changes and sync them incrementally
+            sync: true,
+            forwards: self.forward.clone(),
+            watch: watcher::Settings {
+                trigger: self.trigger,
+                debounce: Duration::from_millis(self.debounce),
+                poll_interval: Duration::from_millis(self.poll_interval),
+            },
         };
-        let playbook = pipeline::load(&ctx, &self.filename, opt.once).await?;
+
+        // Collect the environment variables injected from command line.
+        let vars = env::collect(&self.env_file, &self.env)?;
+
+        // Reuse the playbook left behind by a crashed session, or create a new one.
+        let playbook =
+            match pipeline::resume(&ctx, &self.filename, utils::assume_yes(self.assume_yes), opt.once, &vars).await? {
+                Some(playbook) => playbook,
+                None => pipeline::load(&ctx, &self.filename, opt.once, &vars).await?,
+            };
 
         // Run dev mode. This will sync the full sources into the server,
         // and then watch for changes and sync them incrementally.
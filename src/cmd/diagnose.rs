@@ -12,12 +12,14 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::path::PathBuf;
 use std::sync::Arc;
 
 use clap::Args;
 
 use crate::context::Context;
-use crate::errors::Result;
+use crate::errors::{Errors, Result};
+use crate::ops::diagnosis;
 
 /// Run a diagnostic on Amphitheatre
 #[derive(Args, Debug)]
@@ -29,7 +31,16 @@ pub struct Cli {
 
     /// Path or URL to the Amphitheatre config file
     #[arg(short, long, env = "AMP_FILENAME")]
-    filename: Option<String>,
+    filename: Option<PathBuf>,
+
+    /// Write a support bundle with the redacted configuration, manifest and logs,
+    /// into the temporary directory unless the file is given
+    #[arg(long, value_name = "FILE", num_args = 0..=1)]
+    bundle: Option<Option<PathBuf>>,
+
+    /// Output the report in JSON format
+    #[arg(long, action = clap::ArgAction::SetTrue, env = "AMP_JSON")]
+    json: bool,
 
     /// Activate profiles by name (prefixed with `-` to disable a profile)
     #[arg(short, long, default_value = "[]", env = "AMP_PROFILE")]
@@ -37,7 +48,25 @@ pub struct Cli {
 }
 
 impl Cli {
-    pub async fn exec(&self, _ctx: Arc<Context>) -> Result<()> {
+    /// The context is optional, the checks of the configuration and the
+    /// context report why it can not be loaded.
+    pub async fn exec(&self, ctx: Option<Arc<Context>>) -> Result<()> {
+        let checks = diagnosis::diagnose(ctx.as_deref(), &self.filename).await;
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&checks).map_err(Errors::JsonSerializeError)?);
+        } else {
+            for check in &checks {
+                println!("{check}");
+            }
+        }
+
+        // The bundle is kept out of the workspace by default, so it's never synced.
+        if let Some(path) = &self.bundle {
+            let path = path.clone().unwrap_or_else(|| std::env::temp_dir().join("amp-support.tar"));
+            diagnosis::bundle(ctx.as_deref(), &self.filename, &checks, &path).await?;
+        }
+
         Ok(())
     }
 }
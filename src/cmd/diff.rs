@@ -0,0 +1,115 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::PathBuf;
+use std::sync::Arc;
+
+use amp_common::resource::PlaybookSpec;
+use clap::Args;
+use colored::Colorize;
+
+use crate::context::Context;
+use crate::errors::{Errors, Result};
+use crate::ops::{differ, env, pipeline};
+use crate::state::State;
+
+/// Show the differences between the local character and the deployed one
+#[derive(Args, Debug)]
+#[command(after_help = super::cli::AFTER_HELP_STRING)]
+pub struct Cli {
+    /// The ID of the playbook, defaults to the playbook of the current session,
+    /// or the one deployed with the name of the character
+    playbook: Option<String>,
+
+    /// Set environment variables for the character (KEY=VALUE), overrides the manifest
+    #[arg(short, long = "env", value_name = "KEY=VALUE", value_parser = env::parse_pair)]
+    env: Vec<(String, String)>,
+
+    /// Read environment variables from a file, overrides the manifest
+    #[arg(long, value_name = "FILE", env = "AMP_ENV_FILE")]
+    env_file: Vec<PathBuf>,
+
+    /// Exit with a non-zero code if there are differences
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    exit_code: bool,
+
+    /// Path or URL to the Amphitheatre config file
+    #[arg(short, long, env = "AMP_FILENAME")]
+    filename: Option<PathBuf>,
+}
+
+impl Cli {
+    pub async fn exec(&self, ctx: Arc<Context>) -> Result<()> {
+        let vars = env::collect(&self.env_file, &self.env)?;
+        pipeline::prepare(&ctx.session, &self.filename).await?;
+        let name = ctx.session.character.read().await.as_ref().map(|c| c.meta.name.clone()).unwrap_or_default();
+
+        let playbook = match &self.playbook {
+            Some(id) => ctx.client.playbooks().get(id).await.map_err(Errors::ClientError)?,
+            None => deployed(&ctx, &name).await?,
+        };
+
+        // Resolve the local character the same way as the playbook was created,
+        // by `amp deploy`, or by `amp run` and `amp dev`.
+        let is_deployed = pipeline::is_deployed(&playbook);
+        let mut local = match is_deployed {
+            true => pipeline::deployable(&ctx.session, &vars).await?,
+            false => pipeline::spec(&ctx.session, false, &vars).await?,
+        };
+        let name = local.meta.name.clone();
+        let remote = playbook.characters.iter().flatten().find(|c| c.meta.name == name);
+        let remote = remote.ok_or_else(|| Errors::NotFoundDeployedCharacter(name.clone(), playbook.id.clone()))?;
+
+        // `amp deploy` skips the build and pins the image from the build artifacts, which
+        // are not known here, so the build and the image are left out of the comparison.
+        if is_deployed {
+            local.build = None;
+            local.deploy.get_or_insert_with(Default::default).image =
+                remote.deploy.as_ref().and_then(|deploy| deploy.image.clone());
+        }
+
+        let local = serde_json::to_value(&local).map_err(Errors::JsonSerializeError)?;
+        let remote = serde_json::to_value(remote).map_err(Errors::JsonSerializeError)?;
+        let changes = differ::compare(&remote, &local);
+
+        println!("{}", format!("--- playbook #{} ({})", playbook.id, name).dimmed());
+        let source = self.filename.clone().or(ctx.session.workspace.read().await.clone()).unwrap_or_default();
+        println!("{}", format!("+++ {}", source.display()).dimmed());
+        if changes.is_empty() {
+            println!("The deployed character is up to date");
+            return Ok(());
+        }
+        for change in &changes {
+            println!("{change}");
+        }
+
+        match self.exit_code {
+            true => Err(Errors::CharacterDrift(changes.len())),
+            false => Ok(()),
+        }
+    }
+}
+
+/// Find the playbook of the current session, or the one deployed by `amp deploy`.
+async fn deployed(ctx: &Context, name: &str) -> Result<PlaybookSpec> {
+    // There is no session for the manifest fetched from a URL.
+    let remote = *ctx.session.remote.read().await;
+    if let Some(workspace) = ctx.session.workspace.read().await.clone().filter(|_| !remote) {
+        if let Some(state) = State::load(&workspace)? {
+            return ctx.client.playbooks().get(&state.playbook).await.map_err(Errors::ClientError);
+        }
+    }
+
+    pipeline::deployed(ctx, name).await?.ok_or(Errors::NotFoundSession)
+}
@@ -0,0 +1,68 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use clap::Args;
+
+use crate::context::Context;
+use crate::errors::{Errors, Result};
+use crate::ops::pipeline;
+use crate::ops::terminal::{self, Options};
+
+/// Execute a command in a running character
+#[derive(Args, Debug)]
+#[command(after_help = super::cli::AFTER_HELP_STRING)]
+pub struct Cli {
+    /// The ID of the playbook, defaults to the playbook of the current session
+    playbook: Option<String>,
+
+    /// The name of the character, defaults to the lead character of the playbook
+    character: Option<String>,
+
+    /// Pass stdin to the command
+    #[arg(short = 'i', long, action = clap::ArgAction::SetTrue)]
+    stdin: bool,
+
+    /// Allocate a TTY for the command
+    #[arg(short, long, action = clap::ArgAction::SetTrue)]
+    tty: bool,
+
+    /// The command and its arguments to execute
+    #[arg(last = true, required = true)]
+    command: Vec<String>,
+}
+
+impl Cli {
+    pub async fn exec(&self, ctx: Arc<Context>) -> Result<()> {
+        let playbook = match &self.playbook {
+            Some(id) => ctx.client.playbooks().get(id).await.map_err(Errors::ClientError)?,
+            None => pipeline::current(&ctx).await?,
+        };
+        let character = match &self.character {
+            Some(name) => name.clone(),
+            None => pipeline::lead_name(&playbook).ok_or(Errors::InvalidCharacter)?,
+        };
+
+        let options = Options { command: self.command.clone(), stdin: self.stdin, tty: self.tty };
+        let cluster = ctx.cluster.read().await.clone();
+        let code = terminal::exec(&cluster, &playbook.id, &character, &options).await?;
+
+        // Exit with the same code as the command, once the terminal is restored.
+        match code {
+            0 => Ok(()),
+            code => Err(Errors::CommandExited(code)),
+        }
+    }
+}
@@ -0,0 +1,70 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::errors::{Errors, Result};
+use crate::format::Format;
+use crate::ops::{formatter, pipeline};
+
+/// Format the character manifests in the canonical key order
+#[derive(Args, Debug)]
+#[command(after_help = super::cli::AFTER_HELP_STRING)]
+pub struct Cli {
+    /// The manifests to format, defaults to the one in current or parent directories
+    files: Vec<PathBuf>,
+
+    /// Check if the manifests are formatted without changing them, exits non-zero if not
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    check: bool,
+}
+
+impl Cli {
+    pub fn exec(&self) -> Result<()> {
+        let files = match self.files.is_empty() {
+            true => vec![pipeline::locate(&None)?],
+            false => self.files.clone(),
+        };
+
+        let mut unformatted = 0;
+        for path in &files {
+            if Format::from_path(path) != Format::Toml {
+                return Err(Errors::UnsupportedFormat(path.clone()));
+            }
+
+            let content = fs::read_to_string(path).map_err(Errors::FailedReadManifest)?;
+            let formatted = formatter::format(&content)?;
+            if formatted == content {
+                continue;
+            }
+
+            unformatted += 1;
+            match self.check {
+                true => println!("Would reformat {}", path.display()),
+                false => {
+                    fs::write(path, formatted).map_err(Errors::FailedSaveManifest)?;
+                    println!("Formatted {}", path.display());
+                }
+            }
+        }
+
+        match self.check && unformatted > 0 {
+            true => Err(Errors::UnformattedManifests(unformatted)),
+            false => Ok(()),
+        }
+    }
+}
@@ -13,67 +13,277 @@
 // limitations under the License.
 
 use std::fs;
-use std::sync::Arc;
+use std::path::{Path, PathBuf};
 
 use crate::context::Context;
 use crate::errors::{Errors, Result};
+use crate::format::Format;
+use crate::manifest;
+use crate::ops::detector::{self, table, Settings, DEFAULT_BUILDER};
+use crate::ops::{formatter, importer};
+use crate::utils;
 use amp_common::schema::Character;
 use clap::Args;
 use colored::Colorize;
-use tracing::error;
+use ignore::WalkBuilder;
+use inquire::error::InquireResult;
+use inquire::{Confirm, Select, Text};
 
-const FILE_NAME: &str = ".amp.toml";
+const NAME_PLACEHOLDER: &str = "{{name}}";
 
 /// Create a new Amphitheatre character in an existing directory
 #[derive(Args, Debug)]
 #[command(after_help = super::cli::AFTER_HELP_STRING)]
 pub struct Cli {
-    /// If true, amp will skip yes/no confirmation from the user
-    #[arg(long, action = clap::ArgAction::Set, default_value = "true", env = "AMP_ASSUME_YES")]
-    assume_yes: bool,
+    /// If true, amp will skip yes/no confirmation from the user,
+    /// defaults to true only when the input is not a terminal
+    #[arg(long, action = clap::ArgAction::Set, env = "AMP_ASSUME_YES")]
+    assume_yes: Option<bool>,
     /// File to write generated manifests to
     #[arg(short, long, default_value = ".amp.toml", env = "AMP_FILENAME")]
-    filename: Option<String>,
+    filename: PathBuf,
+    /// The format of the manifest, defaults to the extension of the filename
+    #[arg(long, value_enum, env = "AMP_FORMAT")]
+    format: Option<Format>,
     /// Force the generation of the Amphitheatre character
     #[arg(long, action = clap::ArgAction::SetTrue, env = "AMP_FORCE")]
     force: bool,
     /// Set the character name. Defaults to the directory name.
     #[arg(long, env = "AMP_NAME")]
     name: Option<String>,
+    /// Scaffold from a local template directory or a character in the registry
+    #[arg(long, value_name = "DIR|NAME", env = "AMP_TEMPLATE")]
+    template: Option<String>,
+    /// Convert a docker-compose file, Procfile or skaffold.yaml into characters
+    #[arg(long, value_name = "FILE", conflicts_with = "template")]
+    from: Option<PathBuf>,
 }
 
 impl Cli {
-    pub async fn exec(&self, _ctx: Arc<Context>) -> Result<()> {
-        let path = std::env::current_dir().unwrap();
+    pub async fn exec(&self, interactive: bool) -> Result<()> {
+        let dir = std::env::current_dir().map_err(Errors::FailedSaveManifest)?;
+        let path = dir.join(self.filename());
 
-        let name = self.name.as_deref().unwrap_or_else(|| path.file_name().unwrap().to_str().unwrap());
+        if let Some(from) = &self.from {
+            return self.import(&dir, from);
+        }
 
-        if !self.force && path.join(FILE_NAME).exists() {
-            error!("`amp init` cannot be run on existing Amphitheatre character");
-            std::process::exit(1);
+        if !self.force && path.exists() {
+            return Err(Errors::ExistingManifest(path));
         }
 
-        if let Err(e) = create(name) {
-            error!("Failed to create the character: {}", e.to_string());
-            std::process::exit(1);
+        let mut name = match &self.name {
+            Some(name) => name.clone(),
+            None => dir.file_name().and_then(|n| n.to_str()).unwrap_or("untitled").to_string(),
+        };
+
+        // The files of the local template, copied only after the manifest is written.
+        let mut files = vec![];
+        let character = match &self.template {
+            Some(template) if Path::new(template).is_dir() => {
+                let template = Path::new(template);
+                let source = manifest::lookup(template).ok_or(Errors::NotFoundManifest)?;
+                files = scaffold(template, &source, &dir, self.force)?;
+
+                let (manifest, _) = manifest::compose(&source)?;
+                let mut manifest = toml::Value::Table(manifest);
+                substitute(&mut manifest, &name);
+                let mut character: Character = manifest.try_into().map_err(Errors::TomlDeserializeError)?;
+                character.meta.name.clone_from(&name);
+                character
+            }
+            Some(template) => {
+                // Only the templates from the registry need the server.
+                let ctx = Context::init()?;
+                let mut character = ctx.client.characters().get(template).await.map_err(Errors::ClientError)?;
+                character.meta.name.clone_from(&name);
+                character
+            }
+            None => {
+                // Detect the build and deploy settings from the project layout,
+                // and let the user adjust them in interactive mode.
+                let mut settings = detector::detect(&dir);
+                if let Some(language) = &settings.language {
+                    println!("Detected a {} project", language);
+                }
+                if interactive {
+                    (name, settings) = inquire(name, settings).map_err(Errors::InquireError)?;
+                }
+                generate(&name, |manifest| settings.apply(manifest))?
+            }
+        };
+
+        if !write(&path, &character, utils::assume_yes(self.assume_yes))? {
+            println!("Aborted, the character was not created");
+            return Ok(());
         }
+        copy(&files, &name)?;
 
-        println!("Configuration .amp.toml was created successfully");
+        println!("Configuration {} was created successfully", self.filename().display());
         println!("{}", "You can now run [amp run] to build and deploy your character".green());
         println!("{}", "or [amp dev] to enter development mode, with hot reloading".green());
 
         Ok(())
     }
+
+    /// The file to write, with the extension of the format if it's chosen.
+    fn filename(&self) -> PathBuf {
+        match self.format {
+            Some(format) => self.filename.with_extension(format.extension()),
+            None => self.filename.clone(),
+        }
+    }
+
+    /// Write a character for every service of the file, the manifest is written to
+    /// the given filename if there is only one, otherwise prefixed with the name.
+    fn import(&self, dir: &Path, from: &Path) -> Result<()> {
+        let import = importer::import(from)?;
+        let filename = self.filename();
+        let basename = filename.file_name().and_then(|n| n.to_str()).unwrap_or(".amp.toml").to_string();
+        let target = |name: &str| match import.characters.len() {
+            1 => filename.clone(),
+            _ => filename.with_file_name(format!("{}.{}", name, basename.trim_start_matches('.'))),
+        };
+
+        for imported in &import.characters {
+            let path = dir.join(target(&imported.name));
+            if !self.force && path.exists() {
+                return Err(Errors::ExistingManifest(path));
+            }
+
+            let character = generate(&imported.name, |manifest| {
+                manifest.extend(imported.manifest.clone());
+                for partner in &imported.partners {
+                    let mut spec = toml::Table::new();
+                    spec.insert("path".into(), format!("./{}", target(partner).display()).into());
+                    table(manifest, "partners").insert(partner.clone(), spec.into());
+                }
+            })?;
+
+            match write(&path, &character, utils::assume_yes(self.assume_yes))? {
+                true => println!("Configuration {} was created for {}", path.display(), imported.name),
+                false => println!("Skipped the character {}", imported.name),
+            }
+        }
+
+        if !import.unmapped.is_empty() {
+            println!("{}", "The following settings could not be mapped, please migrate them by hand:".yellow());
+            for item in &import.unmapped {
+                println!("  - {item}");
+            }
+        }
+
+        Ok(())
+    }
 }
 
-fn create(name: &str) -> Result<()> {
+/// Generate the character, and fill the manifest fields with the given function.
+fn generate(name: &str, fill: impl FnOnce(&mut toml::Table)) -> Result<Character> {
     // Init and fill the Manifest fields.
-    let manifest = Character::new(name);
+    let mut manifest = toml::Value::try_from(Character::new(name)).map_err(Errors::TomlSerializeError)?;
+    if let Some(table) = manifest.as_table_mut() {
+        fill(table);
+    }
+
+    // Make sure the filled manifest is still a valid character.
+    manifest.try_into().map_err(Errors::TomlDeserializeError)
+}
+
+/// Write the character in the format of the file extension, returns false if
+/// the user declined to write it after the preview.
+fn write(path: &Path, character: &Character, assume_yes: bool) -> Result<bool> {
+    let serialized = match Format::from_path(path) {
+        Format::Toml => formatter::format(&Format::Toml.serialize(character)?)?,
+        format => format.serialize(character)?,
+    };
+
+    if !assume_yes {
+        println!("{serialized}");
+        let message = format!("Write the character to {}?", path.display());
+        if !Confirm::new(&message).with_default(true).prompt().map_err(Errors::InquireError)? {
+            return Ok(false);
+        }
+    }
+    fs::write(path, serialized).map_err(Errors::FailedSaveManifest)?;
+
+    Ok(true)
+}
+
+/// Plan the files of the template to copy into the directory, except its manifest,
+/// returns the pairs of the source and the target.
+fn scaffold(template: &Path, source: &Path, dir: &Path, force: bool) -> Result<Vec<(PathBuf, PathBuf)>> {
+    let mut files = vec![];
+    for entry in WalkBuilder::new(template).hidden(false).filter_entry(|e| e.file_name() != ".git").build() {
+        let entry = entry.map_err(Errors::WalkError)?;
+        let relative = entry.path().strip_prefix(template).map_err(Errors::FailedStripPrefix)?;
+        if !entry.path().is_file() || entry.path() == source {
+            continue;
+        }
+
+        let target = dir.join(relative);
+        if !force && target.exists() {
+            return Err(Errors::ExistingManifest(target));
+        }
+        files.push((entry.path().to_path_buf(), target));
+    }
+
+    Ok(files)
+}
 
-    // Convert the Manifest to a TOML String.
-    let serialized = toml::to_string(&manifest).map_err(Errors::TomlSerializeError)?;
-    println!("{serialized}");
-    fs::write(FILE_NAME, serialized).map_err(Errors::FailedSaveManifest)?;
+/// Copy the planned files of the template, and substitute the name placeholder
+/// in the text files.
+fn copy(files: &[(PathBuf, PathBuf)], name: &str) -> Result<()> {
+    for (source, target) in files {
+        if let Some(parent) = target.parent() {
+            fs::create_dir_all(parent).map_err(Errors::FailedCopyTemplate)?;
+        }
+
+        let content = fs::read(source).map_err(Errors::FailedCopyTemplate)?;
+        let content = match String::from_utf8(content) {
+            Ok(text) => text.replace(NAME_PLACEHOLDER, name).into_bytes(),
+            Err(err) => err.into_bytes(),
+        };
+        fs::write(target, content).map_err(Errors::FailedCopyTemplate)?;
+    }
 
     Ok(())
 }
+
+/// Substitute the name placeholder in all the strings of the manifest.
+fn substitute(value: &mut toml::Value, name: &str) {
+    match value {
+        toml::Value::String(s) => *s = s.replace(NAME_PLACEHOLDER, name),
+        toml::Value::Array(array) => array.iter_mut().for_each(|v| substitute(v, name)),
+        toml::Value::Table(table) => table.iter_mut().for_each(|(_, v)| substitute(v, name)),
+        _ => {}
+    }
+}
+
+/// Ask the user for each field of the character.
+fn inquire(name: String, mut settings: Settings) -> InquireResult<(String, Settings)> {
+    let name = Text::new("What is the name of the character?").with_default(&name).prompt()?;
+
+    let methods = vec!["buildpacks", "dockerfile"];
+    let cursor = if settings.dockerfile.is_some() { 1 } else { 0 };
+    let method = Select::new("How to build the character?", methods).with_starting_cursor(cursor).prompt()?;
+    if method == "dockerfile" {
+        let default = settings.dockerfile.clone().unwrap_or_else(|| "Dockerfile".into());
+        settings.dockerfile = Some(Text::new("Where is the Dockerfile?").with_default(&default).prompt()?);
+    } else {
+        let default = settings.builder.clone().unwrap_or_else(|| DEFAULT_BUILDER.into());
+        settings.dockerfile = None;
+        settings.builder = Some(Text::new("Which buildpacks builder to use?").with_default(&default).prompt()?);
+    }
+
+    let default = settings.ports.iter().map(|p| p.to_string()).collect::<Vec<_>>().join(",");
+    let ports =
+        Text::new("Which ports does the character listen on? (comma separated)").with_default(&default).prompt()?;
+    settings.ports = ports.split(',').filter_map(|p| p.trim().parse().ok()).collect();
+
+    let default = settings.command.clone().unwrap_or_default();
+    let command =
+        Text::new("What is the command to start the character? (empty for default)").with_default(&default).prompt()?;
+    settings.command = Some(command.trim().to_string()).filter(|c| !c.is_empty());
+
+    Ok((name, settings))
+}
@@ -0,0 +1,80 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+use std::path::PathBuf;
+
+use clap::Args;
+use toml::Table;
+
+use crate::errors::{Errors, Result};
+use crate::format::Format;
+use crate::ops::pipeline;
+
+/// Convert the character manifest between TOML, YAML and JSON
+#[derive(Args, Debug)]
+#[command(after_help = crate::cmd::cli::AFTER_HELP_STRING)]
+pub struct Cli {
+    /// Path to the manifest to convert, defaults to the one in current or parent directories
+    #[arg(short, long, env = "AMP_FILENAME")]
+    filename: Option<PathBuf>,
+
+    /// The format to convert to
+    #[arg(long, value_enum)]
+    to: Format,
+
+    /// File to write the converted manifest, defaults to the manifest with the new extension
+    #[arg(short, long)]
+    output: Option<PathBuf>,
+
+    /// Remove the original manifest after the conversion, it is kept by default
+    #[arg(long, alias = "replace", action = clap::ArgAction::SetTrue)]
+    delete: bool,
+
+    /// Overwrite the output file if it already exists
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    force: bool,
+}
+
+impl Cli {
+    pub fn exec(&self) -> Result<()> {
+        let source = pipeline::locate(&self.filename)?;
+        let target = self.output.clone().unwrap_or_else(|| source.with_extension(self.to.extension()));
+        if target == source {
+            println!("{} is already in {} format", source.display(), self.to.extension());
+            return Ok(());
+        }
+        if !self.force && target.exists() {
+            return Err(Errors::ExistingManifest(target));
+        }
+
+        // Convert through a generic table instead of the character,
+        // so the keys unknown by the schema are kept as well.
+        let content = fs::read_to_string(&source).map_err(Errors::FailedReadManifest)?;
+        let manifest: Table = Format::from_path(&source).deserialize(&content)?;
+        fs::write(&target, self.to.serialize(&manifest)?).map_err(Errors::FailedSaveManifest)?;
+
+        if self.delete {
+            // Make sure the written manifest reads back the same before removing the original.
+            let written = fs::read_to_string(&target).map_err(Errors::FailedReadManifest)?;
+            if self.to.deserialize::<Table>(&written)? != manifest {
+                return Err(Errors::InvalidConversion(target));
+            }
+            fs::remove_file(&source).map_err(Errors::FailedSaveManifest)?;
+        }
+        println!("Converted {} to {}", source.display(), target.display());
+
+        Ok(())
+    }
+}
@@ -21,10 +21,18 @@ pub mod debug;
 pub mod deploy;
 pub mod dev;
 pub mod diagnose;
+pub mod diff;
+pub mod exec;
+pub mod fmt;
 pub mod init;
 pub mod list;
+pub mod manifest;
 pub mod options;
+pub mod port_forward;
 pub mod render;
 pub mod run;
+pub mod schema;
+pub mod sync;
 pub mod test;
+pub mod validate;
 pub mod version;
@@ -0,0 +1,52 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::sync::Arc;
+
+use clap::Args;
+use futures::future::try_join_all;
+
+use crate::context::Context;
+use crate::errors::Result;
+use crate::ops::forwarder::{self, Mapping, Tunnel};
+
+/// Forward one or more local ports to a character of the playbook
+#[derive(Args, Debug)]
+#[command(after_help = super::cli::AFTER_HELP_STRING)]
+pub struct Cli {
+    /// The ID of the playbook
+    playbook: String,
+
+    /// The name of the character
+    character: String,
+
+    /// The ports to forward, in the form of LOCAL:REMOTE or PORT
+    #[arg(required = true, value_name = "LOCAL:REMOTE")]
+    ports: Vec<Mapping>,
+}
+
+impl Cli {
+    pub async fn exec(&self, ctx: Arc<Context>) -> Result<()> {
+        let cluster = ctx.cluster.read().await.clone();
+
+        let forwards = self.ports.iter().map(|mapping| {
+            let tunnel =
+                Tunnel::new(&cluster.server, cluster.token.clone(), &self.playbook, &self.character, mapping.remote);
+            forwarder::forward(*mapping, tunnel)
+        });
+        try_join_all(forwards).await?;
+
+        Ok(())
+    }
+}
@@ -12,40 +12,117 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::sync::Arc;
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
 
 use clap::Args;
+use colored::Colorize;
+use toml::{Table, Value};
 
-use crate::context::Context;
-use crate::errors::Result;
+use crate::context::Session;
+use crate::errors::{Errors, Result};
+use crate::format::Format;
+use crate::manifest;
+use crate::ops::{env, pipeline};
 
-/// Perform all image builds, and output rendered Kubernetes manifests
+/// Output the resolved playbook payload which would be deployed to the server
 #[derive(Args, Debug)]
 #[command(after_help = super::cli::AFTER_HELP_STRING)]
 pub struct Cli {
-    /// If true, amp will skip yes/no confirmation from the user
-    #[arg(long, action = clap::ArgAction::Set, default_value = "true", env = "AMP_ASSUME_YES")]
-    assume_yes: bool,
+    /// Set environment variables for the character (KEY=VALUE), overrides the manifest
+    #[arg(short, long = "env", value_name = "KEY=VALUE", value_parser = env::parse_pair)]
+    env: Vec<(String, String)>,
+
+    /// Read environment variables from a file, overrides the manifest
+    #[arg(long, value_name = "FILE", env = "AMP_ENV_FILE")]
+    env_file: Vec<PathBuf>,
 
     /// Path or URL to the Amphitheatre config file
     #[arg(short, long, env = "AMP_FILENAME")]
-    filename: Option<String>,
+    filename: Option<PathBuf>,
+
+    /// The format of the output, defaults to the extension of the output file or TOML
+    #[arg(long, value_enum, env = "AMP_FORMAT")]
+    format: Option<Format>,
 
-    /// Show the build logs and output
+    /// Show the files the manifest is composed from and the activated profiles, on standard error
     #[arg(long, action = clap::ArgAction::SetTrue, env = "AMP_LOUD")]
     loud: bool,
 
+    /// Print the composed manifest with the file each value comes from, instead of the payload
+    #[arg(long, action = clap::ArgAction::SetTrue)]
+    origins: bool,
+
     /// File to write the changed config (instead of standard output)
     #[arg(short, long, env = "AMP_OUTPUT")]
-    output: Option<String>,
+    output: Option<PathBuf>,
 
     /// Activate profiles by name (prefixed with `-` to disable a profile)
-    #[arg(short, long, default_value = "[]", env = "AMP_PROFILE")]
-    profile: Option<Vec<String>>,
+    #[arg(short, long, value_delimiter = ',', env = "AMP_PROFILE")]
+    profile: Vec<String>,
 }
 
 impl Cli {
-    pub async fn exec(&self, _ctx: Arc<Context>) -> Result<()> {
+    /// Render without a context, the server is never contacted.
+    pub async fn exec(&self, strict: bool) -> Result<()> {
+        let session = Session::default();
+        *session.strict.write().await = strict;
+        *session.profiles.write().await = self.profile.clone();
+
+        let path = pipeline::source(&session, &self.filename).await?;
+        let (manifest, origins) = self.compose(&path)?;
+        if self.origins {
+            return self.origins(&manifest, &origins);
+        }
+        if self.loud {
+            let files: BTreeSet<&PathBuf> = origins.values().collect();
+            files.iter().for_each(|file| eprintln!("{}", format!("# {}", relative(file).display()).dimmed()));
+            self.profile.iter().for_each(|profile| eprintln!("{}", format!("# profile: {profile}").dimmed()));
+        }
+
+        // Resolve the character the same way as `amp deploy` does.
+        let vars = env::collect(&self.env_file, &self.env)?;
+        session.load(&path).await?;
+        let character = pipeline::deployable(&session, &vars).await?;
+
+        let format = self.format.or(self.output.as_deref().map(Format::from_path)).unwrap_or_default();
+        let rendered = format.serialize(&pipeline::deployment(&character))?;
+        match &self.output {
+            Some(path) => fs::write(path, rendered).map_err(Errors::FailedWriteOutput)?,
+            None => println!("{rendered}"),
+        }
+
         Ok(())
     }
+
+    /// Compose the manifest with the profiles activated.
+    fn compose(&self, path: &Path) -> Result<(Table, manifest::Origins)> {
+        let (mut manifest, mut origins) = manifest::compose(path)?;
+        manifest::activate(&mut manifest, &self.profile, &mut origins)?;
+        Ok((manifest, origins))
+    }
+
+    /// Print every value of the composed manifest as a dotted key, with its origin.
+    fn origins(&self, manifest: &Table, origins: &manifest::Origins) -> Result<()> {
+        for (key, origin) in origins {
+            if let Some(value) = lookup(manifest, key) {
+                println!("{} = {}  {}", key, value, format!("# {}", relative(origin).display()).dimmed());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// The path relative to the current directory, for shorter output.
+fn relative(path: &Path) -> &Path {
+    let cwd = std::env::current_dir().unwrap_or_default();
+    path.strip_prefix(&cwd).unwrap_or(path)
+}
+
+fn lookup<'a>(manifest: &'a Table, key: &str) -> Option<&'a Value> {
+    let (parents, last) = key.rsplit_once('.').unwrap_or(("", key));
+    let table = parents.split('.').filter(|k| !k.is_empty()).try_fold(manifest, |table, k| table.get(k)?.as_table())?;
+    table.get(last)
 }
@@ -20,7 +20,7 @@ use std::sync::Arc;
 use crate::context::Context;
 use crate::errors::Result;
 use crate::ops::pipeline::Options;
-use crate::ops::{cleaner, pipeline};
+use crate::ops::{cleaner, env, pipeline};
 
 /// Run a pipeline, build & deploy once
 #[derive(Args, Debug)]
@@ -34,6 +34,14 @@ pub struct Cli {
     #[arg(long, action = clap::ArgAction::Set, default_value = "true", env = "AMP_CLEANUP")]
     cleanup: bool,
 
+    /// Set environment variables for the character (KEY=VALUE), overrides the manifest
+    #[arg(short, long = "env", value_name = "KEY=VALUE", value_parser = env::parse_pair)]
+    env: Vec<(String, String)>,
+
+    /// Read environment variables from a file, overrides the manifest
+    #[arg(long, value_name = "FILE", env = "AMP_ENV_FILE")]
+    env_file: Vec<PathBuf>,
+
     /// Path or URL to the Amphitheatre config file
     #[arg(short, long, env = "AMP_FILENAME")]
     filename: Option<PathBuf>,
@@ -66,6 +74,9 @@ impl Cli {
             tail: self.tail, // toggle log streaming
             live: false,     // sync the sources from local to server
             once: true,      // build & deploy once, then exit
+            sync: false,     // nothing to watch after the run
+            forwards: vec![],
+            watch: Default::default(),
         };
 
         // Create the playbook based on the options
@@ -76,7 +87,8 @@ impl Cli {
             playbook = pipeline::fetch(&ctx, name).await?;
         } else {
             opt.live = true;
-            playbook = pipeline::load(&ctx, &self.filename, opt.once).await?;
+            let vars = env::collect(&self.env_file, &self.env)?;
+            playbook = pipeline::load(&ctx, &self.filename, opt.once, &vars).await?;
         }
 
         // Run the pipeline, build & deploy once.
@@ -0,0 +1,125 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+use std::path::PathBuf;
+
+use amp_common::config::Configuration;
+use amp_common::schema::Character;
+use clap::{Args, ValueEnum};
+use schemars::{schema_for, Schema};
+use serde_json::json;
+
+use crate::errors::{Errors, Result};
+use crate::ops::ignorer::Rules;
+use crate::ops::tester::Test;
+
+/// Print the JSON Schema of the character manifest or the configuration file
+#[derive(Args, Debug)]
+#[command(after_help = SCHEMA_HELP_STRING)]
+pub struct Cli {
+    /// The file to print the schema for
+    #[arg(value_enum, default_value_t = Subject::Character)]
+    subject: Subject,
+
+    /// File to write the schema (instead of standard output)
+    #[arg(short, long, env = "AMP_OUTPUT")]
+    output: Option<PathBuf>,
+}
+
+/// The files described by a schema.
+#[derive(Clone, Copy, Debug, ValueEnum)]
+enum Subject {
+    /// The character manifest, `.amp.toml`
+    Character,
+    /// The CLI configuration file
+    Config,
+}
+
+const SCHEMA_HELP_STRING: &str = "Use the schema in your editor:
+  Even Better TOML / taplo:  add `#:schema ./amp.schema.json` to the top of .amp.toml
+  YAML language server:      add `# yaml-language-server: $schema=./amp.schema.json` to .amp.yaml
+
+Use \"amp options\" for a list of global command-line options (applies to all commands).";
+
+impl Cli {
+    pub fn exec(&self) -> Result<()> {
+        let schema = match self.subject {
+            Subject::Character => character(),
+            Subject::Config => config(),
+        };
+
+        let rendered = serde_json::to_string_pretty(&schema).map_err(Errors::JsonSerializeError)?;
+        match &self.output {
+            Some(path) => fs::write(path, rendered).map_err(Errors::FailedWriteOutput)?,
+            None => println!("{rendered}"),
+        }
+
+        Ok(())
+    }
+}
+
+/// The schema of the character, with the `[sync]`, `[[tests]]` and `[profiles]` tables and the
+/// composition keys only known by the CLI.
+fn character() -> Schema {
+    let mut schema = schema_for!(Character);
+    schema.insert("$id".into(), json!("https://amphitheatre.app/schemas/character.json"));
+    schema.insert("title".into(), json!("Amphitheatre character manifest"));
+
+    let mut tests = schema_for!(Vec<Test>);
+    tests.remove("$schema");
+    tests.insert("description".into(), json!("The tests run by `amp test` against the deployed character"));
+    let mut sync = schema_for!(Rules);
+    sync.remove("$schema");
+    sync.insert(
+        "description".into(),
+        json!("The globs of the files to sync, on top of the .gitignore and .ampignore files"),
+    );
+    if let Some(properties) = schema.get_mut("properties").and_then(|p| p.as_object_mut()) {
+        properties.insert("sync".into(), sync.to_value());
+        properties.insert("tests".into(), tests.to_value());
+        properties.insert(
+            "extends".into(),
+            json!({
+                "description": "The manifest to extend, relative to this manifest",
+                "type": "string"
+            }),
+        );
+        properties.insert(
+            "include".into(),
+            json!({
+                "description": "The fragments to include, relative to this manifest, they override the extended manifest",
+                "type": "array",
+                "items": { "type": "string" }
+            }),
+        );
+        properties.insert(
+            "profiles".into(),
+            json!({
+                "description": "The tables overlaid on the manifest when activated with `--profile`, by name",
+                "type": "object",
+                "additionalProperties": { "type": "object" }
+            }),
+        );
+    }
+
+    schema
+}
+
+fn config() -> Schema {
+    let mut schema = schema_for!(Configuration);
+    schema.insert("$id".into(), json!("https://amphitheatre.app/schemas/config.json"));
+    schema.insert("title".into(), json!("Amphitheatre CLI configuration"));
+    schema
+}
@@ -0,0 +1,47 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::PathBuf;
+
+use clap::Args;
+
+use crate::errors::{Errors, Result};
+use crate::ops::{pipeline, watcher};
+use crate::state::State;
+
+/// Sync the changes queued by dev or debug mode with the manual trigger
+#[derive(Args, Debug)]
+#[command(after_help = super::cli::AFTER_HELP_STRING)]
+pub struct Cli {
+    /// Path or URL to the Amphitheatre config file
+    #[arg(short, long, env = "AMP_FILENAME")]
+    filename: Option<PathBuf>,
+}
+
+impl Cli {
+    pub fn exec(&self) -> Result<()> {
+        let path = pipeline::locate(&self.filename)?;
+        let workspace = path.parent().unwrap_or(&path).to_path_buf();
+        let state = State::load(&workspace)?.ok_or(Errors::NotFoundSession)?;
+        if !state.manual {
+            println!("The running session syncs the changes automatically, there is nothing to request");
+            return Ok(());
+        }
+
+        watcher::request_sync(&workspace)?;
+        println!("Requested the running session to sync the queued changes");
+
+        Ok(())
+    }
+}
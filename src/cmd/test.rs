@@ -12,32 +12,122 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
 use std::sync::Arc;
+use std::time::Duration;
 
+use amp_common::resource::PlaybookSpec;
 use clap::Args;
+use tracing::{error, info};
 
 use crate::context::Context;
-use crate::errors::Result;
+use crate::errors::{Errors, Result};
+use crate::ops::cleaner;
+use crate::ops::pipeline::{self, Options};
+use crate::ops::tester::{self, Outcome, ReportFormat, Test};
 
 /// Run tests against your built application images
 #[derive(Args, Debug)]
 #[command(after_help = super::cli::AFTER_HELP_STRING)]
 pub struct Cli {
-    /// If true, amp will skip yes/no confirmation from the user
-    #[arg(long, action = clap::ArgAction::Set, default_value = "true", env = "AMP_ASSUME_YES")]
-    assume_yes: bool,
-
     /// Path or URL to the Amphitheatre config file
     #[arg(short, long, env = "AMP_FILENAME")]
-    filename: Option<String>,
+    filename: Option<PathBuf>,
+
+    /// The format of the test report
+    #[arg(long, value_enum, default_value_t = ReportFormat::Junit, env = "AMP_REPORT_FORMAT")]
+    format: ReportFormat,
+
+    /// File to write the test report (instead of standard output)
+    #[arg(short, long, env = "AMP_OUTPUT")]
+    output: Option<PathBuf>,
+
+    /// The seconds to wait for the character to be ready before running the tests
+    #[arg(long, value_name = "SECONDS", default_value = "300", env = "AMP_TIMEOUT")]
+    timeout: u64,
 
     /// Activate profiles by name (prefixed with `-` to disable a profile)
-    #[arg(short, long, default_value = "[]", env = "AMP_PROFILE")]
-    profile: Option<Vec<String>>,
+    #[arg(short, long, value_delimiter = ',', env = "AMP_PROFILE")]
+    profile: Vec<String>,
 }
 
 impl Cli {
-    pub async fn exec(&self, _ctx: Arc<Context>) -> Result<()> {
+    pub async fn exec(&self, ctx: Arc<Context>) -> Result<()> {
+        let tests = tester::load(&pipeline::source(&ctx.session, &self.filename).await?)?;
+        if tests.is_empty() {
+            println!("No tests found in the manifest");
+            return Ok(());
+        }
+
+        // Setup handler for for handling Ctrl-C signals, the playbook is always cleaned up.
+        cleaner::setup_signal_handler(ctx.clone(), true);
+
+        // Build a fresh playbook from the local sources, and run the tests against it.
+        *ctx.session.profiles.write().await = self.profile.clone();
+        let playbook = pipeline::load(&ctx, &self.filename, true, &HashMap::new()).await?;
+        ctx.session.playbook.write().await.replace(playbook.clone());
+        let result = self.run(&ctx, playbook, &tests).await;
+
+        // Cleanup the playbook after the tests, even if they failed to start.
+        if let Err(err) = cleaner::try_cleanup_playbook(&ctx).await {
+            error!("Failed to cleanup playbook: {:?}", err);
+        }
+
+        let (name, outcomes) = result?;
+        let report = tester::report(&name, &outcomes, self.format)?;
+        match &self.output {
+            Some(path) => fs::write(path, report).map_err(Errors::FailedWriteOutput)?,
+            None => println!("{report}"),
+        }
+
+        let failures = outcomes.iter().filter(|o| !o.passed()).count();
+        if failures > 0 {
+            return Err(Errors::FailedTests(failures));
+        }
+
         Ok(())
     }
+
+    /// Run the tests once the character is ready, returns its name and the outcomes.
+    async fn run(&self, ctx: &Arc<Context>, playbook: PlaybookSpec, tests: &[Test]) -> Result<(String, Vec<Outcome>)> {
+        let opt = Options {
+            cleanup: false,
+            tail: false,
+            live: true,
+            once: true,
+            sync: false,
+            forwards: vec![],
+            watch: Default::default(),
+        };
+        pipeline::run(ctx, playbook, opt).await?;
+
+        let playbook = ctx.session.playbook.read().await.clone().unwrap();
+        let name = pipeline::lead_name(&playbook).ok_or(Errors::InvalidCharacter)?;
+        let cluster = ctx.cluster.read().await.clone();
+        tester::wait(&cluster, &playbook.id, &name, Duration::from_secs(self.timeout)).await?;
+
+        let mut outcomes = vec![];
+        for test in tests {
+            match tester::run(&cluster, &playbook.id, &name, test).await {
+                Ok(outcome) => {
+                    info!("Test {} {}", outcome.name, if outcome.passed() { "passed" } else { "failed" });
+                    outcomes.push(outcome);
+                }
+                Err(err) => {
+                    error!("Failed to run test {}: {}", test.name, err);
+                    outcomes.push(Outcome {
+                        name: test.name.clone(),
+                        code: -1,
+                        stdout: String::new(),
+                        stderr: err.to_string(),
+                        duration: 0.0,
+                    });
+                }
+            }
+        }
+
+        Ok((name, outcomes))
+    }
 }
@@ -0,0 +1,49 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::PathBuf;
+
+use clap::Args;
+use colored::Colorize;
+
+use crate::errors::{Errors, Result};
+use crate::ops::pipeline;
+use crate::ops::validator::{self, Severity};
+
+/// Validate the character manifest, exits non-zero if it's invalid
+#[derive(Args, Debug)]
+#[command(after_help = super::cli::AFTER_HELP_STRING)]
+pub struct Cli {
+    /// Path or URL to the Amphitheatre config file
+    #[arg(short, long, env = "AMP_FILENAME")]
+    filename: Option<PathBuf>,
+}
+
+impl Cli {
+    pub fn exec(&self) -> Result<()> {
+        let path = pipeline::locate(&self.filename)?;
+        let problems = validator::validate(&path, false)?;
+        for problem in &problems {
+            println!("{}:{}", path.display(), problem);
+        }
+
+        match problems.iter().filter(|p| p.severity == Severity::Error).count() {
+            0 => {
+                println!("{} {} is valid", "✓".green(), path.display());
+                Ok(())
+            }
+            n => Err(Errors::InvalidManifest(n)),
+        }
+    }
+}
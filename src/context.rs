@@ -23,24 +23,34 @@ use amp_common::{
 use tokio::sync::RwLock;
 
 use crate::errors::{Errors, Result};
+use crate::manifest;
 
 /// Session holds the current session state
 #[derive(Default, Debug)]
 #[allow(dead_code)]
 pub struct Session {
     pub workspace: RwLock<Option<PathBuf>>,
+    /// The manifest file the character is loaded from
+    pub manifest: RwLock<Option<PathBuf>>,
     pub character: RwLock<Option<Character>>,
     pub playbook: RwLock<Option<PlaybookSpec>>,
     pub actor: RwLock<Option<ActorSpec>>,
+    /// Whether the manifest is fetched from a URL, without a local workspace
+    pub remote: RwLock<bool>,
+    /// Fail on undefined variables in the manifest instead of replacing them
+    pub strict: RwLock<bool>,
+    /// The profiles activated when loading the character
+    pub profiles: RwLock<Vec<String>>,
 }
 
 impl Session {
     /// Load the character from the specified file.
     pub async fn load(&self, path: &PathBuf) -> Result<()> {
         let workspace = path.parent().unwrap().to_path_buf();
-        let character = Character::load(path).map_err(Errors::FailedLoadManifest)?;
+        let character = manifest::load(path, &self.profiles.read().await)?;
 
         self.workspace.write().await.replace(workspace);
+        self.manifest.write().await.replace(path.clone());
         self.character.write().await.replace(character);
 
         Ok(())
@@ -74,7 +84,7 @@ impl Context {
 }
 
 /// Get the current context from the configuration
-fn get_context(configuration: &Configuration) -> Result<Cluster> {
+pub fn get_context(configuration: &Configuration) -> Result<Cluster> {
     if let Some(context) = &configuration.context {
         if let Some((_, current)) = context.current() {
             return Ok(current.to_owned());
@@ -12,9 +12,9 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::path::StripPrefixError;
+use std::path::{PathBuf, StripPrefixError};
 
-use amp_common::{filesystem, http};
+use amp_common::http;
 use thiserror::Error;
 
 pub type Result<T> = std::result::Result<T, Errors>;
@@ -33,9 +33,6 @@ pub enum Errors {
     #[error("Client error: {0}")]
     ClientError(http::HTTPError),
 
-    #[error("Failed to load manifest: {0}")]
-    FailedLoadManifest(anyhow::Error),
-
     #[error("Failed to delete playbook: {0}")]
     FailedDeletePlaybook(String),
 
@@ -64,6 +61,9 @@ pub enum Errors {
     #[error("Walk directory error: {0}")]
     WalkError(ignore::Error),
 
+    #[error("Invalid sync pattern: {0}")]
+    InvalidSyncPattern(ignore::Error),
+
     #[error("Failed to strip prefix: {0}")]
     FailedStripPrefix(StripPrefixError),
 
@@ -88,9 +88,154 @@ pub enum Errors {
     #[error("Failed to add context: {0}")]
     FailedAddContext(anyhow::Error),
 
-    #[error("Not found character in current or parent directories: {0}")]
-    NotFoundManifest(filesystem::Error),
+    #[error("Not found character in current or parent directories")]
+    NotFoundManifest,
 
     #[error("Invalid character")]
     InvalidCharacter,
+
+    #[error("Failed to deserialize toml: {0}")]
+    TomlDeserializeError(toml::de::Error),
+
+    #[error("Failed to load session state: {0}")]
+    FailedLoadState(std::io::Error),
+
+    #[error("Failed to save session state: {0}")]
+    FailedSaveState(std::io::Error),
+
+    #[error("Failed to load env file {0:?}: {1}")]
+    FailedLoadEnvFile(PathBuf, std::io::Error),
+
+    #[error("Failed to listen on local port: {0}")]
+    FailedListenPort(std::io::Error),
+
+    #[error("Failed to open tunnel: {0}")]
+    FailedOpenTunnel(String),
+
+    #[error("Tunnel error: {0}")]
+    TunnelError(std::io::Error),
+
+    #[error("Several playbooks are titled {0} ({1}), please specify the playbook")]
+    AmbiguousPlaybook(String, String),
+
+    #[error("The command exited with code {0}")]
+    CommandExited(i32),
+
+    #[error("Not found session in current workspace, please specify the playbook or run `amp dev` first")]
+    NotFoundSession,
+
+    #[error("Not found supported debugger protocol in the workspace, tried: {0}")]
+    NotFoundDebuggerProtocol(String),
+
+    #[error("Failed to load build artifacts: {0}")]
+    FailedLoadBuildArtifacts(std::io::Error),
+
+    #[error("Failed to deserialize json: {0}")]
+    JsonDeserializeError(serde_json::Error),
+
+    #[error("Not found image for character: {0}")]
+    NotFoundImage(String),
+
+    #[error("Failed to update playbook: {0}")]
+    FailedUpdatePlaybook(http::HTTPError),
+
+    #[error("Failed to serialize json: {0}")]
+    JsonSerializeError(serde_json::Error),
+
+    #[error("Failed to serialize yaml: {0}")]
+    YamlSerializeError(serde_yaml::Error),
+
+    #[error("Failed to deserialize yaml: {0}")]
+    YamlDeserializeError(serde_yaml::Error),
+
+    #[error("Failed to write output: {0}")]
+    FailedWriteOutput(std::io::Error),
+
+    #[error("The character {0} is not ready: {1}")]
+    NotReadyCharacter(String, String),
+
+    #[error("Invalid test: {0}")]
+    InvalidTest(String),
+
+    #[error("Failed to run test: {0}")]
+    FailedRunTest(std::io::Error),
+
+    #[error("{0} test(s) failed")]
+    FailedTests(usize),
+
+    #[error("Failed to create support bundle: {0}")]
+    FailedCreateBundle(std::io::Error),
+
+    #[error("`amp init` cannot be run on existing file: {0:?}")]
+    ExistingManifest(PathBuf),
+
+    #[error("Failed to copy template: {0}")]
+    FailedCopyTemplate(std::io::Error),
+
+    #[error("Failed to load {0:?} to import: {1}")]
+    FailedLoadImport(PathBuf, std::io::Error),
+
+    #[error("Unsupported file to import: {0:?}, expected docker-compose, Procfile or skaffold")]
+    UnsupportedImport(PathBuf),
+
+    #[error("Invalid file to import: {0}")]
+    InvalidImport(String),
+
+    #[error("Failed to read manifest: {0}")]
+    FailedReadManifest(std::io::Error),
+
+    #[error("Invalid manifest, {0} error(s) found")]
+    InvalidManifest(usize),
+
+    #[error("Undefined variable in manifest: {0}")]
+    UndefinedVariable(String),
+
+    #[error("Invalid variable interpolation: {0}")]
+    InvalidInterpolation(String),
+
+    #[error("Manifest extends or includes itself: {0:?}")]
+    CyclicManifest(PathBuf),
+
+    #[error("Invalid manifest composition: {0}")]
+    InvalidComposition(String),
+
+    #[error("Not found profile `{0}` in the manifest")]
+    NotFoundProfile(String),
+
+    #[error("The converted manifest {0:?} does not read back the same, the original is kept")]
+    InvalidConversion(PathBuf),
+
+    #[error("Failed to fetch manifest: {0}")]
+    FailedFetchManifest(reqwest::Error),
+
+    #[error("Checksum mismatch for {0}: expected {1}, got {2}")]
+    ChecksumMismatch(String, String, String),
+
+    #[error("Failed to cache manifest: {0}")]
+    FailedCacheManifest(std::io::Error),
+
+    #[error("Failed to parse toml: {0}")]
+    TomlEditError(toml_edit::TomlError),
+
+    #[error("Only TOML manifests can be formatted: {0:?}")]
+    UnsupportedFormat(PathBuf),
+
+    #[error("{0} manifest(s) are not formatted, run `amp fmt` to fix them")]
+    UnformattedManifests(usize),
+
+    #[error("Not found character {0} in playbook {1}")]
+    NotFoundDeployedCharacter(String, String),
+
+    #[error("The deployed character differs from the local one in {0} field(s)")]
+    CharacterDrift(usize),
+}
+
+impl Errors {
+    /// The exit code of the process for the error.
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Errors::CommandExited(code) => *code,
+            _ => 1,
+        }
+    }
 }
@@ -0,0 +1,68 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+use crate::errors::{Errors, Result};
+
+/// The formats for the manifests and rendered outputs.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum Format {
+    #[default]
+    Toml,
+    Json,
+    Yaml,
+}
+
+impl Format {
+    /// Choose the format by the extension of the file, defaults to TOML.
+    pub fn from_path(path: &Path) -> Format {
+        match path.extension().and_then(|e| e.to_str()) {
+            Some("json") => Format::Json,
+            Some("yaml") | Some("yml") => Format::Yaml,
+            _ => Format::Toml,
+        }
+    }
+
+    /// The file extension of this format.
+    pub fn extension(&self) -> &'static str {
+        match self {
+            Format::Toml => "toml",
+            Format::Json => "json",
+            Format::Yaml => "yaml",
+        }
+    }
+
+    /// Deserialize the value from a string of this format.
+    pub fn deserialize<T: DeserializeOwned>(&self, content: &str) -> Result<T> {
+        match self {
+            Format::Toml => toml::from_str(content).map_err(Errors::TomlDeserializeError),
+            Format::Json => serde_json::from_str(content).map_err(Errors::JsonDeserializeError),
+            Format::Yaml => serde_yaml::from_str(content).map_err(Errors::YamlDeserializeError),
+        }
+    }
+
+    /// Serialize the value into a string of this format.
+    pub fn serialize<T: Serialize>(&self, value: &T) -> Result<String> {
+        match self {
+            Format::Toml => toml::to_string(value).map_err(Errors::TomlSerializeError),
+            Format::Json => serde_json::to_string_pretty(value).map_err(Errors::JsonSerializeError),
+            Format::Yaml => serde_yaml::to_string(value).map_err(Errors::YamlSerializeError),
+        }
+    }
+}
@@ -0,0 +1,56 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs::{self, File, OpenOptions};
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+use amp_common::config::Configuration;
+use tracing::metadata::LevelFilter;
+use tracing_subscriber::layer::SubscriberExt;
+use tracing_subscriber::util::SubscriberInitExt;
+use tracing_subscriber::{fmt, EnvFilter, Layer};
+
+const LOG_FILE: &str = "amp.log";
+/// The environment variable to set the filter of the log file, e.g. `amp=trace`.
+const LOG_FILE_FILTER: &str = "AMP_LOG";
+/// The log file is truncated when it grows larger than this size.
+const MAX_LOG_SIZE: u64 = 5 * 1024 * 1024;
+
+/// Initialize the logging, print to the terminal and keep the logs in a file,
+/// at the info level unless `AMP_LOG` asks for more.
+pub fn init() {
+    let filter = EnvFilter::builder().with_default_directive(LevelFilter::INFO.into()).from_env_lossy();
+    let stdout = fmt::layer().without_time().with_target(false).with_filter(filter);
+
+    let file = open().map(|file| {
+        let filter = EnvFilter::try_from_env(LOG_FILE_FILTER).unwrap_or_else(|_| EnvFilter::new("amp=info"));
+        fmt::layer().with_ansi(false).with_writer(Mutex::new(file)).with_filter(filter)
+    });
+
+    tracing_subscriber::registry().with(stdout).with(file).init();
+}
+
+/// The path of the log file, next to the configuration file.
+pub fn path() -> Option<PathBuf> {
+    Configuration::path().ok()?.parent().map(|p| p.join(LOG_FILE))
+}
+
+fn open() -> Option<File> {
+    let path = path()?;
+    fs::create_dir_all(path.parent()?).ok()?;
+
+    let truncate = fs::metadata(&path).map(|m| m.len() > MAX_LOG_SIZE).unwrap_or(false);
+    OpenOptions::new().create(true).write(true).append(!truncate).truncate(truncate).open(path).ok()
+}
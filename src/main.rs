@@ -15,30 +15,27 @@
 mod cmd;
 mod context;
 mod errors;
+mod format;
+mod logging;
+mod manifest;
 mod ops;
 mod platform;
+mod state;
 mod utils;
 
-use std::sync::Arc;
-
 use clap::Parser;
-use context::Context;
 use errors::Result;
 use tracing::error;
-use tracing::metadata::LevelFilter;
-use tracing_subscriber::EnvFilter;
 
 use crate::cmd::cli::Cli;
 
 #[tokio::main]
 async fn main() -> Result<()> {
-    let filter = EnvFilter::builder().with_default_directive(LevelFilter::INFO.into()).from_env_lossy();
-    tracing_subscriber::fmt().without_time().with_target(false).with_env_filter(filter).init();
+    logging::init();
 
-    let ctx = Arc::new(Context::init()?);
-    if let Err(err) = Cli::parse().exec(ctx).await {
+    if let Err(err) = Cli::parse().exec().await {
         error!("{:#}", err);
-        std::process::exit(1);
+        std::process::exit(err.exit_code());
     }
 
     Ok(())
@@ -0,0 +1,325 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use amp_common::config::Configuration;
+use amp_common::schema::Character;
+use sha2::{Digest, Sha256};
+use toml::{Table, Value};
+use tracing::{debug, warn};
+
+use crate::errors::{Errors, Result};
+use crate::format::Format;
+
+/// The names of the character manifest, in the order of precedence.
+pub const FILE_NAMES: [&str; 8] =
+    [".amp.toml", ".amp.yaml", ".amp.yml", ".amp.json", "amp.toml", "amp.yaml", "amp.yml", "amp.json"];
+
+/// Find the manifest in the directory or its parents.
+pub fn find(dir: &Path) -> Result<PathBuf> {
+    dir.ancestors().find_map(lookup).ok_or(Errors::NotFoundManifest)
+}
+
+/// Look up the manifest in the directory only.
+pub fn lookup(dir: &Path) -> Option<PathBuf> {
+    FILE_NAMES.iter().map(|name| dir.join(name)).find(|path| path.is_file())
+}
+
+/// The directory for the manifests fetched from URLs, next to the configuration.
+const CACHE_DIR: &str = "cache/manifests";
+/// The fragment of the URL pinning the checksum of the manifest.
+const CHECKSUM_FRAGMENT: &str = "#sha256=";
+
+/// Whether the filename is an HTTP(S) URL instead of a local path.
+pub fn is_remote(filename: &Path) -> bool {
+    let filename = filename.to_string_lossy();
+    filename.starts_with("http://") || filename.starts_with("https://")
+}
+
+/// Fetch the manifest from the URL into the local cache, and return the cached file.
+/// The checksum pinned with `#sha256=` is verified, and a pinned manifest already
+/// in the cache is used without fetching it again.
+pub async fn fetch(url: &str) -> Result<PathBuf> {
+    let (url, checksum) = match url.split_once(CHECKSUM_FRAGMENT) {
+        Some((url, checksum)) => (url, Some(checksum.to_lowercase())),
+        None => (url, None),
+    };
+
+    let dir = cache_dir()?;
+    let name = url.rsplit('/').next().unwrap_or_default();
+    let extension = Path::new(name).extension().and_then(|e| e.to_str()).unwrap_or("toml");
+    let path = dir.join(format!("{}.{}", sha256(url.as_bytes()), extension));
+
+    if let (Some(checksum), Ok(content)) = (&checksum, fs::read(&path)) {
+        if &sha256(&content) == checksum {
+            debug!("Using the cached manifest {:?} for {}", path, url);
+            return Ok(path);
+        }
+    }
+
+    let content = match download(url).await {
+        Ok(content) => content,
+        // Fall back to the cached manifest if the server is not reachable.
+        Err(err) if checksum.is_none() && path.is_file() => {
+            warn!("Failed to fetch {}: {}, using the cached manifest", url, err);
+            return Ok(path);
+        }
+        Err(err) => return Err(err),
+    };
+
+    if let Some(checksum) = checksum {
+        let actual = sha256(&content);
+        if actual != checksum {
+            return Err(Errors::ChecksumMismatch(url.to_string(), checksum, actual));
+        }
+    }
+
+    fs::create_dir_all(&dir).map_err(Errors::FailedCacheManifest)?;
+    fs::write(&path, content).map_err(Errors::FailedCacheManifest)?;
+    debug!("Fetched {} into {:?}", url, path);
+
+    Ok(path)
+}
+
+fn cache_dir() -> Result<PathBuf> {
+    let path = Configuration::path().map_err(Errors::InvalidConfigPath)?;
+    Ok(path.parent().unwrap_or(Path::new(".")).join(CACHE_DIR))
+}
+
+/// Whether the manifest is fetched from a URL into the local cache.
+fn is_cached(path: &Path) -> bool {
+    cache_dir().is_ok_and(|dir| path.starts_with(dir))
+}
+
+async fn download(url: &str) -> Result<Vec<u8>> {
+    let response = reqwest::get(url).await.and_then(|r| r.error_for_status()).map_err(Errors::FailedFetchManifest)?;
+    let bytes = response.bytes().await.map_err(Errors::FailedFetchManifest)?;
+    Ok(bytes.to_vec())
+}
+
+fn sha256(content: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// The key of the manifest to extend, relative to the manifest itself.
+const EXTENDS: &str = "extends";
+/// The key of the fragments to include, relative to the manifest itself.
+const INCLUDE: &str = "include";
+/// The key of the profiles, the tables overlaid on the manifest when activated.
+const PROFILES: &str = "profiles";
+
+/// The file each value of the composed manifest comes from, by the dotted key.
+pub type Origins = BTreeMap<String, PathBuf>;
+
+/// Load the character from the manifest with the given profiles activated,
+/// the format is chosen by the extension.
+pub fn load(path: &Path, profiles: &[String]) -> Result<Character> {
+    let (mut manifest, mut origins) = compose(path)?;
+    activate(&mut manifest, profiles, &mut origins)?;
+    let character = Value::Table(manifest).try_into().map_err(Errors::TomlDeserializeError)?;
+    debug!("Loaded character from {:?}", path);
+
+    Ok(character)
+}
+
+/// Read the manifest as a table, without composing it.
+pub fn read(path: &Path) -> Result<Table> {
+    let content = fs::read_to_string(path).map_err(Errors::FailedReadManifest)?;
+    Format::from_path(path).deserialize(&content)
+}
+
+/// Compose the manifest with the one it extends and the fragments it includes,
+/// the manifest overrides the fragments, which override the extended manifest.
+pub fn compose(path: &Path) -> Result<(Table, Origins)> {
+    compose_with(path, &mut vec![])
+}
+
+fn compose_with(path: &Path, visited: &mut Vec<PathBuf>) -> Result<(Table, Origins)> {
+    let canonical = dunce::canonicalize(path).map_err(Errors::FailedReadManifest)?;
+    if visited.contains(&canonical) {
+        return Err(Errors::CyclicManifest(canonical));
+    }
+    visited.push(canonical);
+
+    let mut manifest = read(path)?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+    let mut composed = Table::new();
+    let mut origins = Origins::new();
+
+    // The paths are relative to the manifest, which a manifest fetched from a URL doesn't have.
+    if is_cached(path) && (manifest.contains_key(EXTENDS) || manifest.contains_key(INCLUDE)) {
+        let message = format!("`{EXTENDS}` and `{INCLUDE}` are not supported in the manifests fetched from URLs");
+        return Err(Errors::InvalidComposition(message));
+    }
+
+    if let Some(extends) = manifest.remove(EXTENDS) {
+        let base = extends.as_str().ok_or_else(|| Errors::InvalidComposition(format!("`{EXTENDS}` must be a path")))?;
+        let (base, base_origins) = compose_with(&dir.join(base), visited)?;
+        overlay(&mut composed, base, &base_origins, &mut origins);
+    }
+    if let Some(include) = manifest.remove(INCLUDE) {
+        let fragments = include
+            .as_array()
+            .ok_or_else(|| Errors::InvalidComposition(format!("`{INCLUDE}` must be a list of paths")))?;
+        for fragment in fragments {
+            let fragment = fragment
+                .as_str()
+                .ok_or_else(|| Errors::InvalidComposition(format!("`{INCLUDE}` must be a list of paths")))?;
+            let (table, fragment_origins) = compose_with(&dir.join(fragment), visited)?;
+            overlay(&mut composed, table, &fragment_origins, &mut origins);
+        }
+    }
+
+    merge(&mut composed, manifest, "", path, &mut origins);
+    visited.pop();
+
+    Ok((composed, origins))
+}
+
+/// Merge the composed table into the base, its values keep the origins they were composed with.
+fn overlay(base: &mut Table, table: Table, table_origins: &Origins, origins: &mut Origins) {
+    // The values are recorded without an origin first, then given the origin they were composed with.
+    merge(base, table, "", Path::new(""), origins);
+    for (key, origin) in origins.iter_mut().filter(|(_, origin)| origin.as_os_str().is_empty()) {
+        *origin = table_origins.get(key).cloned().unwrap_or_default();
+    }
+}
+
+/// Activate the profiles by name in order, a name prefixed with `-` deactivates
+/// the profile activated before it. The values of the profiles keep their origins,
+/// and the profiles are removed from the manifest.
+pub fn activate(manifest: &mut Table, names: &[String], origins: &mut Origins) -> Result<()> {
+    let mut profiles = match manifest.remove(PROFILES) {
+        Some(Value::Table(profiles)) => profiles,
+        Some(_) => return Err(Errors::InvalidComposition(format!("`{PROFILES}` must be a table"))),
+        None => Table::new(),
+    };
+
+    let mut active: Vec<&str> = vec![];
+    for name in names {
+        match name.strip_prefix('-') {
+            Some(name) => active.retain(|n| *n != name),
+            None if !active.contains(&name.as_str()) => active.push(name),
+            None => {}
+        }
+    }
+
+    for name in active {
+        let profile = match profiles.remove(name) {
+            Some(Value::Table(profile)) => profile,
+            _ => return Err(Errors::NotFoundProfile(name.to_string())),
+        };
+        debug!("Activating the profile {}", name);
+
+        let prefix = format!("{PROFILES}.{name}.");
+        let profile_origins: Origins =
+            origins.iter().filter_map(|(k, o)| Some((k.strip_prefix(&prefix)?.to_string(), o.clone()))).collect();
+        overlay(manifest, profile, &profile_origins, origins);
+    }
+    origins.retain(|key, _| !key.starts_with(&format!("{PROFILES}.")));
+
+    Ok(())
+}
+
+/// Merge the tables recursively, the other values of the overlay replace the base.
+fn merge(base: &mut Table, overlay: Table, prefix: &str, origin: &Path, origins: &mut Origins) {
+    for (key, value) in overlay {
+        let dotted = match prefix {
+            "" => key.clone(),
+            _ => format!("{prefix}.{key}"),
+        };
+        match (base.get_mut(&key), value) {
+            (Some(Value::Table(base)), Value::Table(overlay)) => merge(base, overlay, &dotted, origin, origins),
+            (_, value) => {
+                // The values replaced by a table or a scalar don't come from their old origins anymore.
+                origins.retain(|k, _| !k.starts_with(&format!("{dotted}.")));
+                record(&value, &dotted, origin, origins);
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+fn record(value: &Value, dotted: &str, origin: &Path, origins: &mut Origins) {
+    match value {
+        Value::Table(table) => table.iter().for_each(|(k, v)| record(v, &format!("{dotted}.{k}"), origin, origins)),
+        _ => {
+            origins.insert(dotted.to_string(), origin.to_path_buf());
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+
+    #[test]
+    fn test_find_manifest() {
+        let dir = utils::workspace(&[("amp.json", "{}"), (".amp.yaml", ""), ("src/main.rs", "")]);
+        let nested = dir.path().join("src");
+
+        assert_eq!(find(&nested).unwrap(), dir.path().join(".amp.yaml"));
+        assert_eq!(lookup(&nested), None);
+    }
+
+    #[test]
+    fn test_compose_manifest() {
+        let dir = utils::workspace(&[
+            ("base.toml", "[deploy]\nimage = \"base\"\ncommand = \"run\"\n"),
+            ("env.toml", "[deploy.env]\nMODE = \"prod\"\n"),
+            (
+                "service/.amp.toml",
+                "extends = \"../base.toml\"\ninclude = [\"../env.toml\"]\n\n[deploy]\nimage = \"service\"\n",
+            ),
+        ]);
+        let workspace = dir.path();
+        let service = workspace.join("service");
+
+        let (manifest, origins) = compose(&service.join(".amp.toml")).unwrap();
+        assert_eq!(manifest["deploy"]["image"].as_str(), Some("service"));
+        assert_eq!(manifest["deploy"]["command"].as_str(), Some("run"));
+        assert_eq!(manifest["deploy"]["env"]["MODE"].as_str(), Some("prod"));
+        assert!(!manifest.contains_key("extends"));
+        assert_eq!(origins["deploy.image"], service.join(".amp.toml"));
+        assert_eq!(origins["deploy.command"], service.join("../base.toml"));
+        assert_eq!(origins["deploy.env.MODE"], service.join("../env.toml"));
+
+        fs::write(workspace.join("base.toml"), "extends = \"service/.amp.toml\"\n").unwrap();
+        assert!(matches!(compose(&service.join(".amp.toml")), Err(Errors::CyclicManifest(_))));
+    }
+
+    #[test]
+    fn test_activate_profiles() {
+        let dir = utils::workspace(&[(
+            ".amp.toml",
+            "[deploy]\nimage = \"dev\"\n\n[profiles.prod.deploy]\nimage = \"prod\"\n\n[profiles.debug.deploy]\ncommand = \"dlv\"\n",
+        )]);
+        let path = dir.path().join(".amp.toml");
+
+        let (mut manifest, mut origins) = compose(&path).unwrap();
+        activate(&mut manifest, &["prod".into(), "debug".into(), "-debug".into()], &mut origins).unwrap();
+        assert_eq!(manifest["deploy"]["image"].as_str(), Some("prod"));
+        assert!(manifest["deploy"].get("command").is_none());
+        assert!(!manifest.contains_key("profiles"));
+        assert_eq!(origins["deploy.image"], path);
+        assert!(!origins.contains_key("profiles.prod.deploy.image"));
+
+        let (mut manifest, mut origins) = compose(&path).unwrap();
+        assert!(matches!(activate(&mut manifest, &["staging".into()], &mut origins), Err(Errors::NotFoundProfile(_))));
+    }
+}
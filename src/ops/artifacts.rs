@@ -0,0 +1,124 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+use amp_common::resource::CharacterSpec;
+use serde::Deserialize;
+use tracing::debug;
+
+use crate::errors::{Errors, Result};
+
+/// The build artifacts file, as written by the `--file-output` option of the builders.
+#[derive(Deserialize, Debug)]
+struct BuildArtifacts {
+    builds: Vec<Build>,
+}
+
+#[derive(Deserialize, Debug)]
+#[serde(rename_all = "camelCase")]
+struct Build {
+    image_name: String,
+    tag: String,
+}
+
+/// Parse an image reference from the command line, in the form of `NAME=IMAGE`,
+/// or `IMAGE` which is used for the lead character.
+pub fn parse_image(s: &str) -> std::result::Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, image)) if !name.is_empty() && !image.is_empty() => Ok((name.to_string(), image.to_string())),
+        Some(_) => Err(format!("invalid NAME=IMAGE: `{s}`")),
+        None => Ok((String::new(), s.to_string())),
+    }
+}
+
+/// Load the images from the build artifacts file.
+pub fn load(path: &Path) -> Result<HashMap<String, String>> {
+    let content = fs::read_to_string(path).map_err(Errors::FailedLoadBuildArtifacts)?;
+    let artifacts: BuildArtifacts = serde_json::from_str(&content).map_err(Errors::JsonDeserializeError)?;
+    debug!("The build artifacts are: {:?}", artifacts);
+
+    Ok(artifacts.builds.into_iter().map(|b| (b.image_name, b.tag)).collect())
+}
+
+/// Use the pre-built image for the character and skip the build stage.
+pub fn apply(character: &mut CharacterSpec, images: &HashMap<String, String>) -> Result<()> {
+    let name = &character.meta.name;
+    let configured = character.deploy.as_ref().and_then(|deploy| deploy.image.as_deref());
+    let image = find(images, name, configured).ok_or_else(|| Errors::NotFoundImage(name.clone()))?.clone();
+    debug!("Deploying character {} with image {}", name, image);
+
+    character.build = None;
+    character.deploy.get_or_insert_with(Default::default).image = Some(image);
+
+    Ok(())
+}
+
+/// Find the image of the character, by its name, by the repository of its configured
+/// image, by the last path segment of the image names, e.g. `api` for `gcr.io/x/api`,
+/// and finally the image given without a name.
+fn find<'a>(images: &'a HashMap<String, String>, name: &str, configured: Option<&str>) -> Option<&'a String> {
+    images
+        .get(name)
+        .or_else(|| configured.and_then(|image| images.get(repository(image))))
+        .or_else(|| {
+            let mut matched = images.iter().filter(|(k, _)| repository(k).rsplit('/').next() == Some(name));
+            matched.next().map(|(_, image)| image)
+        })
+        .or_else(|| images.get(""))
+}
+
+/// The repository of the image reference, without the tag and digest.
+fn repository(image: &str) -> &str {
+    let image = image.split_once('@').map_or(image, |(repository, _)| repository);
+    // the colon before the last slash is the port of the registry, e.g. `localhost:5000/api`.
+    match image.rfind(':') {
+        Some(i) if !image[i..].contains('/') => &image[..i],
+        _ => image,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+
+    #[test]
+    fn test_parse_image() {
+        assert_eq!(parse_image("api=registry/api:sha"), Ok(("api".into(), "registry/api:sha".into())));
+        assert_eq!(parse_image("registry/api:sha"), Ok(("".into(), "registry/api:sha".into())));
+        assert!(parse_image("api=").is_err());
+    }
+
+    #[test]
+    fn test_load_build_artifacts() {
+        let dir = utils::workspace(&[("build.json", r#"{"builds":[{"imageName":"api","tag":"registry/api:sha"}]}"#)]);
+
+        let images = load(&dir.path().join("build.json")).unwrap();
+        assert_eq!(images.get("api"), Some(&"registry/api:sha".to_string()));
+    }
+
+    #[test]
+    fn test_find_image() {
+        let images = HashMap::from([("gcr.io/x/api".to_string(), "gcr.io/x/api:sha".to_string())]);
+        assert_eq!(find(&images, "api", None), Some(&"gcr.io/x/api:sha".to_string()));
+        assert_eq!(find(&images, "web", Some("gcr.io/x/api:latest")), Some(&"gcr.io/x/api:sha".to_string()));
+        assert_eq!(find(&images, "web", None), None);
+
+        assert_eq!(repository("localhost:5000/x/api:v1@sha256:abc"), "localhost:5000/x/api");
+        assert_eq!(repository("localhost:5000/x/api"), "localhost:5000/x/api");
+    }
+}
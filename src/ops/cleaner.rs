@@ -19,6 +19,7 @@ use tracing::{info, warn};
 
 use crate::context::Context;
 use crate::errors::{Errors, Result};
+use crate::state::State;
 
 /// Setup handler for for handling Ctrl-C signals.
 pub fn setup_signal_handler(ctx: Arc<Context>, cleanup: bool) {
@@ -62,5 +63,13 @@ pub async fn try_cleanup_playbook(ctx: &Arc<Context>) -> Result<()> {
     // print success message
     info!("Deleted playbook {}", pid);
 
+    // The playbook is gone, so there is nothing left to resume, unless the
+    // state belongs to another session in the same workspace.
+    if let Some(workspace) = ctx.session.workspace.read().await.as_ref() {
+        if State::load(workspace)?.is_some_and(|state| &state.playbook == pid) {
+            State::remove(workspace)?;
+        }
+    }
+
     Ok(())
 }
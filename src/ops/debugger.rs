@@ -0,0 +1,153 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fmt::Display;
+use std::path::Path;
+
+use clap::ValueEnum;
+use serde_json::{json, Value};
+
+/// The debugger protocols supported by amp debug.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum Protocol {
+    /// Delve, the debugger for Go
+    Dlv,
+    /// Java Debug Wire Protocol, for the JVM languages
+    Jdwp,
+    /// debugpy, the debugger for Python
+    Debugpy,
+    /// The Node.js inspector protocol
+    Nodejs,
+}
+
+impl Protocol {
+    /// All the protocols in the default priority order.
+    pub const ALL: [Protocol; 4] = [Protocol::Dlv, Protocol::Jdwp, Protocol::Debugpy, Protocol::Nodejs];
+
+    /// The port the debugger listens on inside the actor.
+    pub fn port(&self) -> u16 {
+        match self {
+            Protocol::Dlv => 56268,
+            Protocol::Jdwp => 5005,
+            Protocol::Debugpy => 5678,
+            Protocol::Nodejs => 9229,
+        }
+    }
+
+    /// The files in the workspace which indicate the runtime supports this protocol.
+    fn markers(&self) -> &'static [&'static str] {
+        match self {
+            Protocol::Dlv => &["go.mod"],
+            Protocol::Jdwp => &["pom.xml", "build.gradle", "build.gradle.kts"],
+            Protocol::Debugpy => &["pyproject.toml", "requirements.txt", "setup.py", "Pipfile"],
+            Protocol::Nodejs => &["package.json"],
+        }
+    }
+
+    /// The environment variables to start the actor in debug mode, only the JVM
+    /// and Node.js pick up the debugger options from the environment.
+    pub fn env(&self) -> HashMap<String, String> {
+        let port = self.port();
+        let mut vars = HashMap::new();
+
+        match self {
+            Protocol::Jdwp => {
+                let options = format!("-agentlib:jdwp=transport=dt_socket,server=y,suspend=n,address=*:{port}");
+                vars.insert("JAVA_TOOL_OPTIONS".into(), options);
+            }
+            Protocol::Nodejs => {
+                vars.insert("NODE_OPTIONS".into(), format!("--inspect=0.0.0.0:{port}"));
+            }
+            Protocol::Dlv | Protocol::Debugpy => {}
+        }
+
+        vars
+    }
+
+    /// The command to start the process under the debugger, for the runtimes
+    /// which can not be configured through the environment.
+    pub fn launch(&self) -> Option<String> {
+        let port = self.port();
+        match self {
+            Protocol::Dlv => Some(format!(
+                "dlv exec --headless --listen=:{port} --api-version=2 --accept-multiclient --continue <binary>"
+            )),
+            Protocol::Debugpy => Some(format!("python -m debugpy --listen 0.0.0.0:{port} <script>")),
+            Protocol::Jdwp | Protocol::Nodejs => None,
+        }
+    }
+
+    /// The attach configuration for VS Code compatible IDEs, connecting to the forwarded port.
+    pub fn attach(&self, name: &str, port: u16) -> Value {
+        let name = format!("Attach to {name}");
+        match self {
+            Protocol::Dlv => json!({
+                "name": name, "type": "go", "request": "attach", "mode": "remote",
+                "host": "127.0.0.1", "port": port,
+            }),
+            Protocol::Jdwp => json!({
+                "name": name, "type": "java", "request": "attach",
+                "hostName": "127.0.0.1", "port": port,
+            }),
+            Protocol::Debugpy => json!({
+                "name": name, "type": "debugpy", "request": "attach",
+                "connect": { "host": "127.0.0.1", "port": port },
+            }),
+            Protocol::Nodejs => json!({
+                "name": name, "type": "node", "request": "attach",
+                "address": "127.0.0.1", "port": port,
+            }),
+        }
+    }
+}
+
+impl Display for Protocol {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Protocol::Dlv => "dlv",
+            Protocol::Jdwp => "jdwp",
+            Protocol::Debugpy => "debugpy",
+            Protocol::Nodejs => "nodejs",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+/// Choose the first protocol in the priority list supported by the workspace,
+/// all the protocols are considered in the default order if the list is empty.
+pub fn negotiate(workspace: &Path, priorities: &[Protocol]) -> Option<Protocol> {
+    let priorities = match priorities.is_empty() {
+        true => &Protocol::ALL[..],
+        false => priorities,
+    };
+
+    priorities.iter().copied().find(|p| p.markers().iter().any(|m| workspace.join(m).exists()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+
+    #[test]
+    fn test_negotiate_protocol() {
+        let dir = utils::workspace(&[("package.json", "{}"), ("go.mod", "module example")]);
+        let workspace = dir.path();
+
+        assert_eq!(negotiate(workspace, &[]), Some(Protocol::Dlv));
+        assert_eq!(negotiate(workspace, &[Protocol::Nodejs, Protocol::Dlv]), Some(Protocol::Nodejs));
+        assert_eq!(negotiate(workspace, &[Protocol::Jdwp]), None);
+    }
+}
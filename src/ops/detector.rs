@@ -0,0 +1,169 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+use std::path::Path;
+
+use toml::{Table, Value};
+
+/// The builder used when the character is built with buildpacks.
+pub const DEFAULT_BUILDER: &str = "gcr.io/buildpacks/builder:v1";
+
+/// The build and deploy settings detected from the project layout.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Settings {
+    /// The detected language of the project
+    pub language: Option<String>,
+    /// The Dockerfile to build with, buildpacks are used if it's none
+    pub dockerfile: Option<String>,
+    /// The buildpacks builder image
+    pub builder: Option<String>,
+    /// The ports the character listens on
+    pub ports: Vec<u16>,
+    /// The command to start the character
+    pub command: Option<String>,
+}
+
+impl Settings {
+    /// Fill the build and deploy fields of the manifest with the settings.
+    pub fn apply(&self, manifest: &mut Table) {
+        let build = table(manifest, "build");
+        match &self.dockerfile {
+            Some(dockerfile) => {
+                let mut config = Table::new();
+                config.insert("context".into(), ".".into());
+                config.insert("dockerfile".into(), dockerfile.as_str().into());
+                build.insert("dockerfile".into(), config.into());
+            }
+            None => {
+                let mut config = Table::new();
+                config.insert("builder".into(), self.builder.as_deref().unwrap_or(DEFAULT_BUILDER).into());
+                build.insert("buildpacks".into(), config.into());
+            }
+        }
+
+        let deploy = table(manifest, "deploy");
+        if let Some(command) = &self.command {
+            deploy.insert("command".into(), command.as_str().into());
+        }
+        services(manifest, &self.ports);
+    }
+}
+
+/// Expose the ports with a cluster service in the deploy table of the manifest.
+pub fn services(manifest: &mut Table, ports: &[u16]) {
+    if ports.is_empty() {
+        return;
+    }
+    let ports: Vec<Value> = ports
+        .iter()
+        .map(|port| {
+            let mut spec = Table::new();
+            spec.insert("port".into(), Value::Integer(*port as i64));
+            spec.insert("expose".into(), true.into());
+            spec.into()
+        })
+        .collect();
+
+    let mut service = Table::new();
+    service.insert("kind".into(), "ClusterIP".into());
+    service.insert("ports".into(), ports.into());
+    table(manifest, "deploy").insert("services".into(), Value::Array(vec![service.into()]));
+}
+
+/// Get the table of the key in the manifest, creating it if missing.
+pub fn table<'a>(manifest: &'a mut Table, key: &str) -> &'a mut Table {
+    let value = manifest.entry(key).or_insert_with(|| Table::new().into());
+    if !value.is_table() {
+        *value = Table::new().into();
+    }
+    value.as_table_mut().unwrap()
+}
+
+/// Detect the build and deploy settings from the common project layouts.
+pub fn detect(dir: &Path) -> Settings {
+    let mut settings = Settings::default();
+
+    let languages: [(&str, &[&str], u16); 5] = [
+        ("rust", &["Cargo.toml"], 8080),
+        ("node", &["package.json"], 3000),
+        ("go", &["go.mod"], 8080),
+        ("java", &["pom.xml", "build.gradle", "build.gradle.kts"], 8080),
+        ("python", &["pyproject.toml", "requirements.txt"], 8000),
+    ];
+    if let Some((language, _, port)) = languages.iter().find(|(_, m, _)| m.iter().any(|f| dir.join(f).exists())) {
+        settings.language = Some(language.to_string());
+        settings.ports = vec![*port];
+    }
+
+    if settings.language.as_deref() == Some("node") && has_start_script(&dir.join("package.json")) {
+        settings.command = Some("npm start".into());
+    }
+
+    // The Dockerfile takes precedence over buildpacks, and knows the ports better.
+    let dockerfile = dir.join("Dockerfile");
+    if let Ok(content) = fs::read_to_string(&dockerfile) {
+        settings.dockerfile = Some("Dockerfile".into());
+        let ports = exposed_ports(&content);
+        if !ports.is_empty() {
+            settings.ports = ports;
+        }
+    } else if settings.language.is_some() {
+        settings.builder = Some(DEFAULT_BUILDER.into());
+    }
+
+    settings
+}
+
+fn has_start_script(path: &Path) -> bool {
+    let content = fs::read_to_string(path).unwrap_or_default();
+    let package: serde_json::Value = serde_json::from_str(&content).unwrap_or_default();
+    package.pointer("/scripts/start").is_some()
+}
+
+/// Get the ports from the `EXPOSE` instructions of the Dockerfile.
+pub fn exposed_ports(content: &str) -> Vec<u16> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter_map(|line| line.get(..7).filter(|p| p.eq_ignore_ascii_case("EXPOSE ")).map(|_| &line[7..]))
+        .flat_map(str::split_whitespace)
+        .filter_map(|port| port.split('/').next()?.parse().ok())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_exposed_ports() {
+        let content = "FROM golang:1.22\nEXPOSE 8080\nexpose 9090/tcp 53/udp\nCMD [\"/app\"]\n";
+        assert_eq!(exposed_ports(content), vec![8080, 9090, 53]);
+
+        let content = "FROM node:20\n# 日本語の説明\nEXPOSE 3000\n";
+        assert_eq!(exposed_ports(content), vec![3000]);
+    }
+
+    #[test]
+    fn test_apply_settings() {
+        let settings = Settings { ports: vec![3000], command: Some("npm start".into()), ..Default::default() };
+        let mut manifest = Table::new();
+        settings.apply(&mut manifest);
+
+        assert_eq!(manifest["build"]["buildpacks"]["builder"].as_str(), Some(DEFAULT_BUILDER));
+        assert_eq!(manifest["deploy"]["command"].as_str(), Some("npm start"));
+        assert_eq!(manifest["deploy"]["services"][0]["ports"][0]["port"].as_integer(), Some(3000));
+    }
+}
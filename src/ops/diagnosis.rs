@@ -0,0 +1,418 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::Display;
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
+
+use amp_common::config::Configuration;
+use colored::Colorize;
+use ignore::WalkBuilder;
+use serde::Serialize;
+use tar::{Builder, Header};
+use tracing::info;
+
+use crate::context::Context;
+use crate::errors::{Errors, Result};
+use crate::format::Format;
+use crate::logging;
+use crate::ops::env::{self, REDACTED};
+use crate::ops::ignorer::Matcher;
+use crate::ops::pipeline;
+use crate::utils;
+
+/// Warn if the workspace to sync is larger than this size.
+const LARGE_WORKSPACE_SIZE: u64 = 100 * 1024 * 1024;
+/// Only the most recent logs are included in the bundle.
+const MAX_BUNDLE_LOG_SIZE: usize = 1024 * 1024;
+
+/// The status of a check.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Status {
+    Pass,
+    Warn,
+    Fail,
+}
+
+/// The result of a check, with a hint to fix it if it's not passed.
+#[derive(Serialize, Debug, Clone)]
+pub struct Check {
+    pub name: String,
+    pub status: Status,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub hint: Option<String>,
+}
+
+impl Check {
+    fn pass(name: &str, message: impl Into<String>) -> Self {
+        Self { name: name.into(), status: Status::Pass, message: message.into(), hint: None }
+    }
+
+    fn warn(name: &str, message: impl Into<String>, hint: &str) -> Self {
+        Self { name: name.into(), status: Status::Warn, message: message.into(), hint: Some(hint.into()) }
+    }
+
+    fn fail(name: &str, message: impl Into<String>, hint: &str) -> Self {
+        Self { name: name.into(), status: Status::Fail, message: message.into(), hint: Some(hint.into()) }
+    }
+}
+
+impl Display for Check {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let status = match self.status {
+            Status::Pass => "PASS".green(),
+            Status::Warn => "WARN".yellow(),
+            Status::Fail => "FAIL".red(),
+        };
+        write!(f, "[{}] {:<12} {}", status, self.name, self.message)?;
+        if let Some(hint) = &self.hint {
+            write!(f, "\n       {} {}", "hint:".dimmed(), hint)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Run all the checks of the environment and the workspace.
+pub async fn diagnose(ctx: Option<&Context>, filename: &Option<PathBuf>) -> Vec<Check> {
+    let mut checks = vec![version(), configuration(), context()];
+    match ctx {
+        Some(ctx) => {
+            checks.push(reachability(ctx).await);
+            checks.push(token(ctx).await);
+        }
+        None => checks.push(Check::warn(
+            "Server",
+            "Skipped without a current context",
+            "Fix the configuration and the context above first",
+        )),
+    }
+
+    let manifest = pipeline::locate(filename).ok();
+    checks.push(self::manifest(&manifest));
+
+    let workspace = match &manifest {
+        Some(path) => path.parent().map(Path::to_path_buf),
+        None => std::env::current_dir().ok(),
+    };
+    if let Some(workspace) = workspace {
+        checks.push(self::workspace(&workspace, manifest.as_deref()));
+        checks.push(watches(directories(&workspace)));
+    }
+
+    checks
+}
+
+fn version() -> Check {
+    let message = format!("amp {} ({}/{})", env!("CARGO_PKG_VERSION"), std::env::consts::OS, std::env::consts::ARCH);
+    Check::pass("Version", message)
+}
+
+fn configuration() -> Check {
+    let path = match Configuration::path() {
+        Ok(path) => path,
+        Err(err) => {
+            return Check::fail("Config", err.to_string(), "Make sure the home directory is accessible");
+        }
+    };
+
+    match Configuration::load(path.clone()) {
+        Ok(_) => Check::pass("Config", format!("{} is loaded", path.display())),
+        Err(err) => Check::fail(
+            "Config",
+            format!("Failed to load {}: {:#}", path.display(), err),
+            "Fix the file, or run `amp context init` to reset it",
+        ),
+    }
+}
+
+fn context() -> Check {
+    let configuration = match Configuration::path().ok().and_then(|path| Configuration::load(path).ok()) {
+        Some(configuration) => configuration,
+        None => return Check::fail("Context", "No configuration to read the context from", "Fix the config first"),
+    };
+
+    match crate::context::get_context(&configuration) {
+        Ok(cluster) => Check::pass("Context", format!("{} ({})", cluster.title, cluster.server)),
+        Err(err) => Check::fail(
+            "Context",
+            err.to_string(),
+            "Select a context with `amp context use`, or create one with `amp context init`",
+        ),
+    }
+}
+
+async fn reachability(ctx: &Context) -> Check {
+    let server = ctx.cluster.read().await.server.clone();
+    let client = reqwest::Client::builder().timeout(Duration::from_secs(5)).build().unwrap_or_default();
+
+    match client.get(&server).send().await {
+        Ok(res) => Check::pass("Server", format!("{} is reachable ({})", server, res.status())),
+        Err(err) => Check::fail(
+            "Server",
+            format!("{} is not reachable: {}", server, err),
+            "Check the network, or the server address with `amp context show`",
+        ),
+    }
+}
+
+async fn token(ctx: &Context) -> Check {
+    if ctx.cluster.read().await.token.is_none() {
+        return Check::warn("Token", "No token in current context", "Use `amp context use` to set up the token");
+    }
+
+    match ctx.client.playbooks().list(None).await {
+        Ok(_) => Check::pass("Token", "The token is accepted by the server"),
+        Err(err) => Check::fail(
+            "Token",
+            format!("The server rejected the request: {}", err),
+            "The token may be invalid or expired, use `amp context use` to create a new context",
+        ),
+    }
+}
+
+fn manifest(path: &Option<PathBuf>) -> Check {
+    let path = match path {
+        Some(path) => path,
+        None => {
+            return Check::warn(
+                "Manifest",
+                "Not found character in current or parent directories",
+                "Run `amp init` to create a character",
+            );
+        }
+    };
+
+    match crate::manifest::load(path, &[]) {
+        Ok(character) => Check::pass("Manifest", format!("{} ({})", path.display(), character.meta.name)),
+        Err(err) => Check::fail("Manifest", format!("{}: {:#}", path.display(), err), "Fix the manifest and try again"),
+    }
+}
+
+/// Check the files to sync after the ignore rules.
+fn workspace(workspace: &Path, manifest: Option<&Path>) -> Check {
+    let entries = match Matcher::load(workspace, manifest).and_then(|m| utils::walk(workspace, &Arc::new(m))) {
+        Ok(entries) => entries,
+        Err(err) => return Check::fail("Workspace", err.to_string(), "Make sure the workspace is readable"),
+    };
+
+    let files: Vec<_> = entries.iter().filter(|e| e.path().is_file()).collect();
+    let size: u64 = files.iter().filter_map(|e| e.metadata().ok()).map(|m| m.len()).sum();
+
+    let message = format!("{} files, {:.1} MiB to sync in {}", files.len(), mib(size), workspace.display());
+    match size > LARGE_WORKSPACE_SIZE {
+        true => Check::warn("Workspace", message, "Add large files and build outputs to .gitignore or .ampignore"),
+        false => Check::pass("Workspace", message),
+    }
+}
+
+/// Count all the directories of the workspace, the recursive watch registers
+/// every one of them, including the ignored ones.
+fn directories(workspace: &Path) -> usize {
+    let walker = WalkBuilder::new(workspace).standard_filters(false).build();
+    walker.filter_map(|entry| entry.ok()).filter(|entry| entry.file_type().is_some_and(|t| t.is_dir())).count()
+}
+
+/// Check the inotify watch limit against the directories to watch.
+#[cfg(target_os = "linux")]
+fn watches(directories: usize) -> Check {
+    let limit = std::fs::read_to_string("/proc/sys/fs/inotify/max_user_watches")
+        .ok()
+        .and_then(|v| v.trim().parse::<usize>().ok());
+
+    match limit {
+        Some(limit) if directories >= limit => Check::fail(
+            "Watches",
+            format!("{} directories to watch, exceeds the limit {}", directories, limit),
+            "Raise fs.inotify.max_user_watches with sysctl, or use `--trigger polling`",
+        ),
+        Some(limit) if directories * 2 >= limit => Check::warn(
+            "Watches",
+            format!("{} directories to watch, close to the limit {}", directories, limit),
+            "Raise fs.inotify.max_user_watches with sysctl",
+        ),
+        Some(limit) => Check::pass("Watches", format!("{} directories to watch, the limit is {}", directories, limit)),
+        None => Check::warn("Watches", "Unknown inotify watch limit", "Make sure /proc is mounted"),
+    }
+}
+
+#[cfg(not(target_os = "linux"))]
+fn watches(directories: usize) -> Check {
+    Check::pass("Watches", format!("{} directories to watch", directories))
+}
+
+/// Collect the diagnostic information into a tarball for support, all the
+/// tokens known in the configuration are redacted from every file in it.
+pub async fn bundle(ctx: Option<&Context>, filename: &Option<PathBuf>, checks: &[Check], path: &Path) -> Result<()> {
+    let configuration = Configuration::path().ok().and_then(|path| Configuration::load(path).ok());
+    let secrets: Vec<String> = match configuration.and_then(|c| c.context) {
+        Some(context) => context.iter().filter_map(|(_, cluster)| cluster.token.clone()).collect(),
+        None => vec![],
+    };
+
+    let file = File::create(path).map_err(Errors::FailedCreateBundle)?;
+    let mut tar = Builder::new(file);
+    let mut append = |name: &str, content: String| -> Result<()> {
+        let content = redact(&content, &secrets);
+        let mut header = Header::new_gnu();
+        header.set_size(content.len() as u64);
+        header.set_mode(0o644);
+        header.set_cksum();
+        tar.append_data(&mut header, name, content.as_bytes()).map_err(Errors::FailedCreateBundle)
+    };
+
+    let version =
+        format!("amp {}\nos: {}\narch: {}\n", env!("CARGO_PKG_VERSION"), std::env::consts::OS, std::env::consts::ARCH);
+    append("version.txt", version)?;
+    append("diagnose.json", serde_json::to_string_pretty(checks).map_err(Errors::JsonSerializeError)?)?;
+
+    // The configuration, with the tokens redacted by key as well.
+    if let Some(content) = Configuration::path().ok().and_then(|p| fs::read_to_string(p).ok()) {
+        append("config.toml", redact_config(&content))?;
+    }
+
+    // The manifest, and the files which would be synced to the server.
+    if let Ok(manifest) = pipeline::locate(filename) {
+        let format = Format::from_path(&manifest);
+        let name = format!("manifest.{}", format.extension());
+        append(&name, redact_manifest(&fs::read_to_string(&manifest).unwrap_or_default(), format))?;
+
+        if let Some(workspace) = manifest.parent() {
+            let files =
+                match Matcher::load(workspace, Some(&manifest)).and_then(|m| utils::walk(workspace, &Arc::new(m))) {
+                    Ok(entries) => entries
+                        .iter()
+                        .filter(|e| e.path().is_file())
+                        .filter_map(|e| e.path().strip_prefix(workspace).ok())
+                        .map(|p| p.display().to_string())
+                        .collect::<Vec<_>>()
+                        .join("\n"),
+                    Err(err) => err.to_string(),
+                };
+            append("files.txt", files)?;
+        }
+    }
+
+    // The recent logs of the CLI.
+    if let Some(content) = logging::path().and_then(|p| fs::read(p).ok()) {
+        let start = content.len().saturating_sub(MAX_BUNDLE_LOG_SIZE);
+        append("amp.log", String::from_utf8_lossy(&content[start..]).to_string())?;
+    }
+
+    // The playbook of the current session.
+    let playbook = match ctx {
+        Some(ctx) => pipeline::current(ctx).await,
+        None => Err(Errors::NotFoundCurrentContext),
+    };
+    let playbook = match playbook {
+        Ok(playbook) => serde_json::to_string_pretty(&env::redacted(&playbook)?).map_err(Errors::JsonSerializeError)?,
+        Err(err) => serde_json::json!({ "error": err.to_string() }).to_string(),
+    };
+    append("playbook.json", playbook)?;
+
+    tar.finish().map_err(Errors::FailedCreateBundle)?;
+    info!("The support bundle is written to {}", path.display());
+
+    Ok(())
+}
+
+/// Replace all the secrets in the content.
+fn redact(content: &str, secrets: &[String]) -> String {
+    let mut content = content.to_string();
+    for secret in secrets.iter().filter(|s| !s.is_empty()) {
+        content = content.replace(secret.as_str(), REDACTED);
+    }
+
+    content
+}
+
+/// Replace the values of the keys which look like secrets in the TOML content.
+fn redact_config(content: &str) -> String {
+    fn walk(value: &mut toml::Value) {
+        match value {
+            toml::Value::Table(table) => {
+                for (key, value) in table.iter_mut() {
+                    let key = key.to_lowercase();
+                    if key.contains("token") || key.contains("secret") || key.contains("password") {
+                        *value = toml::Value::String(REDACTED.into());
+                    } else {
+                        walk(value);
+                    }
+                }
+            }
+            toml::Value::Array(array) => array.iter_mut().for_each(walk),
+            _ => {}
+        }
+    }
+
+    match content.parse::<toml::Value>() {
+        Ok(mut value) => {
+            walk(&mut value);
+            toml::to_string(&value).unwrap_or_default()
+        }
+        Err(_) => REDACTED.to_string(),
+    }
+}
+
+/// Replace the values of the `env` tables in the manifest, in the format of the manifest.
+fn redact_manifest(content: &str, format: Format) -> String {
+    match format.deserialize::<serde_json::Value>(content) {
+        Ok(mut value) => {
+            env::redact(&mut value);
+            format.serialize(&value).unwrap_or_default()
+        }
+        Err(_) => REDACTED.to_string(),
+    }
+}
+
+fn mib(size: u64) -> f64 {
+    size as f64 / 1024.0 / 1024.0
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_redact_config() {
+        let content = r#"
+[context]
+current = "default"
+
+[context.clusters.default]
+title = "Default"
+server = "http://localhost:8170"
+token = "eyJhbGciOi"
+"#;
+
+        let redacted = redact_config(content);
+        assert!(!redacted.contains("eyJhbGciOi"));
+        assert!(redacted.contains("http://localhost:8170"));
+        assert_eq!(redact("Bearer eyJhbGciOi", &["eyJhbGciOi".into()]), "Bearer [REDACTED]");
+    }
+
+    #[test]
+    fn test_redact_manifest() {
+        let content = "[character]\nname = \"api\"\n\n[deploy.env]\nAPI_KEY = \"secret\"\n";
+
+        let redacted = redact_manifest(content, Format::Toml);
+        assert!(!redacted.contains("secret"));
+        assert!(redacted.contains("API_KEY = \"[REDACTED]\""));
+        assert!(redacted.contains("name = \"api\""));
+    }
+}
@@ -0,0 +1,97 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::BTreeSet;
+use std::fmt::Display;
+
+use colored::Colorize;
+use serde_json::Value;
+
+/// The fields of the character compared between the local manifest and the server.
+pub const FIELDS: [&str; 3] = ["build", "deploy", "partners"];
+
+/// A change of a field, by its dotted path.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Change {
+    Added(String, Value),
+    Removed(String, Value),
+    Changed(String, Value, Value),
+}
+
+impl Display for Change {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Change::Added(path, value) => write!(f, "{}", format!("+ {path}: {value}").green()),
+            Change::Removed(path, value) => write!(f, "{}", format!("- {path}: {value}").red()),
+            Change::Changed(path, old, new) => {
+                write!(f, "{} {}", format!("~ {path}: {old}").yellow(), format!("-> {new}").yellow().bold())
+            }
+        }
+    }
+}
+
+/// Compare the fields of the deployed character with the local one.
+pub fn compare(deployed: &Value, local: &Value) -> Vec<Change> {
+    let mut changes = vec![];
+    for field in FIELDS {
+        let (old, new) = (deployed.get(field).unwrap_or(&Value::Null), local.get(field).unwrap_or(&Value::Null));
+        diff(old, new, field, &mut changes);
+    }
+    changes
+}
+
+/// Diff the values recursively, null and missing values are the same.
+pub fn diff(old: &Value, new: &Value, path: &str, changes: &mut Vec<Change>) {
+    match (old, new) {
+        (Value::Object(old), Value::Object(new)) => {
+            let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+            for key in keys {
+                let (a, b) = (old.get(key).unwrap_or(&Value::Null), new.get(key).unwrap_or(&Value::Null));
+                diff(a, b, &format!("{path}.{key}"), changes);
+            }
+        }
+        (Value::Array(old), Value::Array(new)) => {
+            for i in 0..old.len().max(new.len()) {
+                let (a, b) = (old.get(i).unwrap_or(&Value::Null), new.get(i).unwrap_or(&Value::Null));
+                diff(a, b, &format!("{path}[{i}]"), changes);
+            }
+        }
+        _ if old == new => {}
+        (Value::Null, _) => changes.push(Change::Added(path.to_string(), new.clone())),
+        (_, Value::Null) => changes.push(Change::Removed(path.to_string(), old.clone())),
+        _ => changes.push(Change::Changed(path.to_string(), old.clone(), new.clone())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn test_compare_characters() {
+        let deployed = json!({"deploy": {"image": "app:v1", "env": {"MODE": "dev", "OLD": "1"}}, "partners": null});
+        let local = json!({"deploy": {"image": "app:v2", "env": {"MODE": "dev"}}, "partners": {"db": {"path": "."}}});
+
+        assert_eq!(
+            compare(&deployed, &local),
+            vec![
+                Change::Removed("deploy.env.OLD".into(), json!("1")),
+                Change::Changed("deploy.image".into(), json!("app:v1"), json!("app:v2")),
+                Change::Added("partners.db.path".into(), json!(".")),
+            ]
+        );
+    }
+}
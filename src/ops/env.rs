@@ -0,0 +1,159 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+
+use amp_common::resource::CharacterSpec;
+use tracing::debug;
+
+use crate::errors::{Errors, Result};
+
+/// The placeholder of the redacted values.
+pub const REDACTED: &str = "[REDACTED]";
+
+/// Parse a `KEY=VALUE` pair from the command line.
+pub fn parse_pair(s: &str) -> std::result::Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((key, value)) if !key.trim().is_empty() => Ok((key.trim().to_string(), value.to_string())),
+        _ => Err(format!("invalid KEY=VALUE: no `=` found in `{s}`")),
+    }
+}
+
+/// Collect the environment variables from the given env files and pairs,
+/// later files override earlier ones, and pairs override all files.
+pub fn collect(files: &[PathBuf], pairs: &[(String, String)]) -> Result<HashMap<String, String>> {
+    let mut vars = HashMap::new();
+
+    for file in files {
+        let content = fs::read_to_string(file).map_err(|e| Errors::FailedLoadEnvFile(file.clone(), e))?;
+        vars.extend(parse(&content));
+    }
+    vars.extend(pairs.iter().cloned());
+
+    debug!("The environment variables from command line are: {:?}", vars.keys());
+    Ok(vars)
+}
+
+/// Parse the content of a dotenv style file, blank lines, comments
+/// and lines without `=` are skipped, surrounding quotes are removed.
+pub fn parse(content: &str) -> Vec<(String, String)> {
+    let mut vars = vec![];
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+
+        let line = line.strip_prefix("export ").unwrap_or(line);
+        if let Some((key, value)) = line.split_once('=') {
+            vars.push((key.trim().to_string(), unquote(value.trim()).to_string()));
+        }
+    }
+
+    vars
+}
+
+fn unquote(value: &str) -> &str {
+    for quote in ['"', '\''] {
+        if value.len() >= 2 && value.starts_with(quote) && value.ends_with(quote) {
+            return &value[1..value.len() - 1];
+        }
+    }
+
+    value
+}
+
+/// Merge the environment variables into the character, they take precedence
+/// over the values in the manifest.
+pub fn merge(character: &mut CharacterSpec, vars: &HashMap<String, String>) {
+    if vars.is_empty() {
+        return;
+    }
+
+    let deploy = character.deploy.get_or_insert_with(Default::default);
+    deploy.env.get_or_insert_with(Default::default).extend(vars.clone());
+}
+
+/// Replace the values of all the `env` tables in the serialized playbook or
+/// character, so that they can be logged or bundled.
+pub fn redact(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                match (key.as_str(), value) {
+                    ("env", serde_json::Value::Object(env)) => {
+                        env.values_mut().for_each(|v| *v = serde_json::Value::String(REDACTED.into()))
+                    }
+                    (_, value) => redact(value),
+                }
+            }
+        }
+        serde_json::Value::Array(array) => array.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+/// Serialize the value with all the environment variables redacted.
+pub fn redacted<T: serde::Serialize>(value: &T) -> Result<serde_json::Value> {
+    let mut value = serde_json::to_value(value).map_err(Errors::JsonSerializeError)?;
+    redact(&mut value);
+    Ok(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_env_file() {
+        let content = r#"
+# personal settings
+export API_KEY="secret"
+FEATURE_FLAG=on
+EMPTY=
+GREETING='hello world'
+not a variable
+"#;
+
+        assert_eq!(
+            parse(content),
+            vec![
+                ("API_KEY".into(), "secret".into()),
+                ("FEATURE_FLAG".into(), "on".into()),
+                ("EMPTY".into(), "".into()),
+                ("GREETING".into(), "hello world".into()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_pair() {
+        assert_eq!(parse_pair("A=b=c"), Ok(("A".into(), "b=c".into())));
+        assert!(parse_pair("A").is_err());
+        assert!(parse_pair("=b").is_err());
+    }
+
+    #[test]
+    fn test_redact_env() {
+        let mut value = serde_json::json!({
+            "characters": [{"meta": {"name": "api"}, "deploy": {"env": {"API_KEY": "secret"}}}]
+        });
+        redact(&mut value);
+        assert_eq!(value["characters"][0]["deploy"]["env"]["API_KEY"], REDACTED);
+        assert_eq!(value["characters"][0]["meta"]["name"], "api");
+    }
+}
@@ -0,0 +1,133 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::cmp::Ordering;
+
+use toml_edit::{DocumentMut, Item, Table};
+
+use crate::errors::{Errors, Result};
+
+/// The canonical order of the top-level keys, the unknown keys follow alphabetically.
+const ROOT_ORDER: [&str; 8] = ["extends", "include", "character", "build", "deploy", "partners", "sync", "tests"];
+/// The canonical order of the character metadata.
+const CHARACTER_ORDER: [&str; 3] = ["name", "version", "description"];
+
+/// Format the TOML manifest in the canonical key order, with the sections as standard
+/// tables. The comments and the formatting of the values are preserved.
+pub fn format(content: &str) -> Result<String> {
+    let mut document: DocumentMut = content.parse().map_err(Errors::TomlEditError)?;
+    let root = document.as_table_mut();
+
+    normalize(root);
+    sort(root, &[]);
+    let mut position = 1;
+    arrange(root, &mut position);
+
+    let formatted = document.to_string();
+    Ok(format!("{}\n", formatted.trim_matches('\n')))
+}
+
+/// Turn the top-level inline tables and dotted keys into standard tables.
+fn normalize(root: &mut Table) {
+    for (_, item) in root.iter_mut() {
+        if let Some(mut table) = item.as_inline_table_mut().map(|t| t.clone().into_table()) {
+            table.fmt();
+            *item = Item::Table(table);
+        }
+        if let Some(table) = item.as_table_mut() {
+            table.set_dotted(false);
+        }
+    }
+}
+
+/// Sort the keys of the tables recursively.
+fn sort(table: &mut Table, path: &[&str]) {
+    let order: &[&str] = match path {
+        [] => &ROOT_ORDER,
+        ["character"] => &CHARACTER_ORDER,
+        _ => &[],
+    };
+    table.sort_values_by(|a, _, b, _| compare(order, a.get(), b.get()));
+
+    for (key, item) in table.iter_mut() {
+        let path: Vec<&str> = path.iter().copied().chain([key.get()]).collect();
+        match item {
+            Item::Table(table) => sort(table, &path),
+            Item::ArrayOfTables(array) => array.iter_mut().for_each(|t| sort(t, &path)),
+            _ => {}
+        }
+    }
+}
+
+fn compare(order: &[&str], a: &str, b: &str) -> Ordering {
+    let rank = |key: &str| order.iter().position(|k| *k == key).unwrap_or(order.len());
+    rank(a).cmp(&rank(b)).then_with(|| a.cmp(b))
+}
+
+/// Place the tables in the order of their keys, with one blank line before each header.
+fn arrange(table: &mut Table, position: &mut isize) {
+    for (_, item) in table.iter_mut() {
+        let tables: Vec<&mut Table> = match item {
+            Item::Table(table) => vec![table],
+            Item::ArrayOfTables(array) => array.iter_mut().collect(),
+            _ => continue,
+        };
+        for table in tables {
+            table.set_position(Some(*position));
+            *position += 1;
+
+            // Keep the comments before the header, but only one blank line.
+            let prefix = table.decor().prefix().and_then(|p| p.as_str()).unwrap_or_default();
+            let prefix = format!("\n{}", prefix.trim_start());
+            table.decor_mut().set_prefix(prefix);
+            arrange(table, position);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_format_manifest() {
+        let content = r#"character = { version = "0.1.0", name = "demo" }
+[deploy]
+image = "nginx" # the upstream image
+command = "serve"
+
+
+# how to build it
+[build.dockerfile]
+dockerfile = "Dockerfile"
+context = "."
+"#;
+        let expected = r#"[character]
+name = "demo"
+version = "0.1.0"
+
+# how to build it
+[build.dockerfile]
+context = "."
+dockerfile = "Dockerfile"
+
+[deploy]
+command = "serve"
+image = "nginx" # the upstream image
+"#;
+        let formatted = format(content).unwrap();
+        assert_eq!(formatted, expected);
+        assert_eq!(format(&formatted).unwrap(), formatted);
+    }
+}
@@ -0,0 +1,175 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fmt::Display;
+use std::str::FromStr;
+
+use reqwest::Upgraded;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::time::{sleep, Duration};
+use tracing::{debug, info, warn};
+
+use crate::errors::{Errors, Result};
+use crate::utils;
+
+/// How many times to try to open a stream before giving up on a connection.
+const MAX_ATTEMPTS: u32 = 5;
+
+/// A port mapping from a local port to a port of the actor, e.g. `8080:80`.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Mapping {
+    pub local: u16,
+    pub remote: u16,
+}
+
+impl FromStr for Mapping {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        let parse = |v: &str| v.trim().parse::<u16>().map_err(|_| format!("invalid port `{v}` in `{s}`"));
+        match s.split_once(':') {
+            Some((local, remote)) => Ok(Mapping { local: parse(local)?, remote: parse(remote)? }),
+            None => {
+                let port = parse(s)?;
+                Ok(Mapping { local: port, remote: port })
+            }
+        }
+    }
+}
+
+impl Display for Mapping {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}:{}", self.local, self.remote)
+    }
+}
+
+/// Tunnel opens streams to a port of the actor through the server's API.
+#[derive(Clone)]
+pub struct Tunnel {
+    client: reqwest::Client,
+    url: String,
+    token: Option<String>,
+}
+
+impl Tunnel {
+    pub fn new(server: &str, token: Option<String>, pid: &str, name: &str, port: u16) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: format!("{}/v1/actors/{}/{}/forward/{}", server.trim_end_matches('/'), pid, name, port),
+            token,
+        }
+    }
+
+    /// Open a new stream, the server upgrades the request into a raw TCP stream.
+    async fn open(&self) -> Result<Upgraded> {
+        utils::upgrade(self.client.get(&self.url), &self.token, "tcp").await
+    }
+
+    /// Open a new stream, reconnecting with backoff when the stream can not be established.
+    async fn connect(&self) -> Result<Upgraded> {
+        let mut delay = Duration::from_millis(500);
+        let mut attempt = 1;
+
+        loop {
+            match self.open().await {
+                Ok(stream) => return Ok(stream),
+                Err(err) if attempt < MAX_ATTEMPTS => {
+                    warn!("Failed to open stream to {} (attempt {}): {}, retrying...", self.url, attempt, err);
+                    sleep(delay).await;
+                    delay *= 2;
+                    attempt += 1;
+                }
+                Err(err) => return Err(err),
+            }
+        }
+    }
+}
+
+/// Listen on the local port and forward the connections through the tunnel.
+pub async fn forward(mapping: Mapping, tunnel: Tunnel) -> Result<()> {
+    let listener = TcpListener::bind(("127.0.0.1", mapping.local)).await.map_err(Errors::FailedListenPort)?;
+    info!("Forwarding from 127.0.0.1:{} -> {}", mapping.local, mapping.remote);
+
+    serve(listener, tunnel).await
+}
+
+/// Accept the connections from the listener, every connection gets its own stream,
+/// so a dropped stream only breaks the connection using it.
+pub async fn serve(listener: TcpListener, tunnel: Tunnel) -> Result<()> {
+    loop {
+        let (socket, peer) = listener.accept().await.map_err(Errors::FailedListenPort)?;
+        debug!("Accepted connection from {}", peer);
+
+        let tunnel = tunnel.clone();
+        tokio::spawn(async move {
+            if let Err(err) = handle(socket, &tunnel).await {
+                warn!("The connection from {} is closed: {}", peer, err);
+            }
+        });
+    }
+}
+
+async fn handle(mut socket: TcpStream, tunnel: &Tunnel) -> Result<()> {
+    let mut stream = tunnel.connect().await?;
+    let (sent, received) =
+        tokio::io::copy_bidirectional(&mut socket, &mut stream).await.map_err(Errors::TunnelError)?;
+    debug!("The connection is finished, sent {} bytes, received {} bytes", sent, received);
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    use super::*;
+
+    #[test]
+    fn test_parse_mapping() {
+        assert_eq!("8080:80".parse(), Ok(Mapping { local: 8080, remote: 80 }));
+        assert_eq!("3000".parse(), Ok(Mapping { local: 3000, remote: 3000 }));
+        assert!("8080:http".parse::<Mapping>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_forward_through_tunnel() {
+        // A stand-in tunnel endpoint, it upgrades the request and echoes the bytes back.
+        let server = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = server.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (mut socket, _) = server.accept().await.unwrap();
+            let mut buf = vec![0; 4096];
+            let mut read = 0;
+            while !buf[..read].windows(4).any(|w| w == b"\r\n\r\n") {
+                read += socket.read(&mut buf[read..]).await.unwrap();
+            }
+            let response = "HTTP/1.1 101 Switching Protocols\r\nConnection: upgrade\r\nUpgrade: tcp\r\n\r\n";
+            socket.write_all(response.as_bytes()).await.unwrap();
+
+            let (mut reader, mut writer) = socket.split();
+            tokio::io::copy(&mut reader, &mut writer).await.unwrap();
+        });
+
+        let tunnel = Tunnel::new(&format!("http://{addr}"), None, "pid", "name", 80);
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let local = listener.local_addr().unwrap();
+        tokio::spawn(serve(listener, tunnel));
+
+        let mut client = TcpStream::connect(local).await.unwrap();
+        client.write_all(b"ping").await.unwrap();
+        let mut buf = [0; 4];
+        client.read_exact(&mut buf).await.unwrap();
+        assert_eq!(&buf, b"ping");
+    }
+}
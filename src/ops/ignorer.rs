@@ -0,0 +1,265 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex};
+
+use ignore::gitignore::{Gitignore, GitignoreBuilder};
+use ignore::overrides::{Override, OverrideBuilder};
+use ignore::Match;
+use schemars::JsonSchema;
+use serde::Deserialize;
+use tracing::{debug, warn};
+
+use crate::errors::{Errors, Result};
+use crate::manifest;
+use crate::state::STATE_DIR;
+
+/// The file listing the paths not to sync, in the gitignore syntax.
+pub const IGNORE_FILE: &str = ".ampignore";
+/// The ignore files read in every directory, the latter takes precedence.
+const IGNORE_FILES: [&str; 2] = [".gitignore", IGNORE_FILE];
+/// The key of the sync rules in the manifest.
+const SYNC: &str = "sync";
+
+/// The `[sync]` table of the manifest, with globs relative to the workspace.
+#[derive(Deserialize, JsonSchema, Debug, Default, Clone)]
+pub struct Rules {
+    /// Only sync the matching files, even if they, or the directories they are in, are ignored or hidden
+    #[serde(default)]
+    pub include: Vec<String>,
+    /// Never sync the matching paths, takes precedence over `include`
+    #[serde(default)]
+    pub exclude: Vec<String>,
+}
+
+impl Rules {
+    /// Load the sync rules from the manifest file.
+    pub fn load(path: &Path) -> Result<Rules> {
+        let (mut manifest, _) = manifest::compose(path)?;
+        match manifest.remove(SYNC) {
+            Some(rules) => rules.try_into().map_err(Errors::TomlDeserializeError),
+            None => Ok(Rules::default()),
+        }
+    }
+}
+
+/// Decide which files of the workspace are synced to the server, shared by
+/// the initial upload and the watcher so both agree on the same files.
+///
+/// The first rule matching a path wins:
+/// 1. the state directory and `.git` are never synced,
+/// 2. the `sync.include` and `sync.exclude` globs of the manifest,
+/// 3. the `.gitignore` and `.ampignore` files, the nearest directory first,
+/// 4. the global gitignore of git,
+/// 5. the hidden files are not synced.
+///
+/// A path inside an ignored directory is ignored as well. With the `sync.include`
+/// globs, only the files are matched by them, the directories are only ignored by
+/// the rules 1 and 2, or if no glob could match beneath them, so a file in a directory
+/// ignored by the rules 3 to 5 can be included again.
+pub struct Matcher {
+    root: PathBuf,
+    overrides: Override,
+    /// The `sync.include` globs
+    includes: Vec<String>,
+    global: Gitignore,
+    ignores: Mutex<HashMap<PathBuf, Arc<Gitignore>>>,
+}
+
+impl Matcher {
+    /// Create the matcher for the workspace, with the sync rules of the manifest
+    /// the character is loaded from.
+    pub fn load(workspace: &Path, manifest: Option<&Path>) -> Result<Matcher> {
+        let rules = match manifest {
+            Some(path) => Rules::load(path)?,
+            None => Rules::default(),
+        };
+        Matcher::new(workspace, &rules)
+    }
+
+    /// Create the matcher for the root with the given sync rules.
+    pub fn new(root: &Path, rules: &Rules) -> Result<Matcher> {
+        let mut builder = OverrideBuilder::new(root);
+        for glob in &rules.include {
+            builder.add(glob).map_err(Errors::InvalidSyncPattern)?;
+        }
+        for glob in &rules.exclude {
+            builder.add(&format!("!{glob}")).map_err(Errors::InvalidSyncPattern)?;
+        }
+        let overrides = builder.build().map_err(Errors::InvalidSyncPattern)?;
+
+        let (global, err) = Gitignore::global();
+        if let Some(err) = err {
+            debug!("Failed to load the global gitignore: {}", err);
+        }
+
+        let includes = rules.include.clone();
+        Ok(Matcher { root: root.to_path_buf(), overrides, includes, global, ignores: Mutex::default() })
+    }
+
+    /// Whether the path, or any directory it's in, is not synced.
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return true;
+        };
+
+        let mut paths: Vec<&Path> = relative.ancestors().filter(|p| !p.as_os_str().is_empty()).collect();
+        paths.reverse();
+        let last = paths.len().saturating_sub(1);
+        let ignored = paths.iter().enumerate().any(|(i, p)| self.matched(p, i < last || is_dir));
+        if ignored {
+            debug!("The file is ignored: {:?}", relative);
+        }
+
+        ignored
+    }
+
+    /// Forget the cached rules of the directory if the path is one of its ignore files.
+    pub fn reload(&self, path: &Path) {
+        let Ok(relative) = path.strip_prefix(&self.root) else {
+            return;
+        };
+        let is_ignore_file = relative.file_name().is_some_and(|name| IGNORE_FILES.iter().any(|f| name == *f));
+        if let (true, Some(dir)) = (is_ignore_file, relative.parent()) {
+            self.ignores.lock().unwrap().remove(dir);
+        }
+    }
+
+    /// Match the path relative to the root, regardless of its directories.
+    fn matched(&self, relative: &Path, is_dir: bool) -> bool {
+        if relative.starts_with(STATE_DIR) || relative.starts_with(".git") {
+            return true;
+        }
+
+        match self.overrides.matched(relative, is_dir) {
+            Match::Whitelist(_) => return false,
+            Match::Ignore(_) => return true,
+            Match::None => {}
+        }
+        if is_dir && !self.includes.is_empty() {
+            return !self.includes_beneath(relative);
+        }
+
+        for dir in relative.parent().into_iter().flat_map(Path::ancestors) {
+            let name = relative.strip_prefix(dir).unwrap_or(relative);
+            match self.ignore(dir).matched(name, is_dir) {
+                Match::Whitelist(_) => return false,
+                Match::Ignore(_) => return true,
+                Match::None => {}
+            }
+        }
+
+        if self.global.matched(relative, is_dir).is_ignore() {
+            return true;
+        }
+
+        relative.file_name().is_some_and(|name| name.to_string_lossy().starts_with('.'))
+    }
+
+    /// Whether any `sync.include` glob could match a path beneath the directory, the
+    /// globs without a slash match at any depth, the others are matched component by
+    /// component from the root, a component with wildcards matches any name.
+    fn includes_beneath(&self, dir: &Path) -> bool {
+        self.includes.iter().any(|glob| {
+            let glob = glob.trim_end_matches('/');
+            if !glob.contains('/') {
+                return true;
+            }
+
+            let mut parts = glob.trim_start_matches('/').split('/');
+            for name in dir.iter().map(|name| name.to_string_lossy()) {
+                match parts.next() {
+                    // the glob matches the directory or one of its parents, or any depth.
+                    None | Some("**") => return true,
+                    Some(part) if part == name || part.contains(['*', '?', '[', '{']) => {}
+                    Some(_) => return false,
+                }
+            }
+            true
+        })
+    }
+
+    /// The rules of the ignore files in the directory, relative to the root.
+    fn ignore(&self, dir: &Path) -> Arc<Gitignore> {
+        let mut ignores = self.ignores.lock().unwrap();
+        let ignore = ignores.entry(dir.to_path_buf()).or_insert_with(|| {
+            let dir = self.root.join(dir);
+            let mut builder = GitignoreBuilder::new(&dir);
+            for path in IGNORE_FILES.iter().map(|name| dir.join(name)).filter(|p| p.is_file()) {
+                if let Some(err) = builder.add(&path) {
+                    warn!("Failed to read {:?}: {}", path, err);
+                }
+            }
+            Arc::new(builder.build().unwrap_or_else(|err| {
+                warn!("Invalid ignore rules in {:?}: {}", dir, err);
+                Gitignore::empty()
+            }))
+        });
+
+        ignore.clone()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::fs;
+
+    use super::*;
+    use crate::utils;
+
+    #[test]
+    fn test_ignore_rules() {
+        let dir = utils::workspace(&[
+            (".gitignore", "*.log\ntarget/\n"),
+            (".ampignore", "!debug.log\n"),
+            ("src/.gitignore", "generated/\n"),
+        ]);
+        let root = dir.path();
+
+        let rules = Rules { include: vec![], exclude: vec!["*.bak".into()] };
+        let matcher = Matcher::new(root, &rules).unwrap();
+        let ignored = |path: &str| matcher.is_ignored(&root.join(path), false);
+
+        assert!(!ignored("src/main.rs"));
+        assert!(ignored("app.log"));
+        assert!(!ignored("debug.log"));
+        assert!(ignored("target/release/app"));
+        assert!(ignored("src/generated/api.rs"));
+        assert!(ignored("src/main.rs.bak"));
+        assert!(ignored(".env"));
+        assert!(ignored(".amp/session.toml"));
+
+        let rules = Rules { include: vec!["src/**".into(), ".env".into()], exclude: vec![] };
+        let matcher = Matcher::new(root, &rules).unwrap();
+        assert!(!matcher.is_ignored(&root.join("src/main.rs"), false));
+        assert!(!matcher.is_ignored(&root.join(".env"), false));
+        assert!(matcher.is_ignored(&root.join("README.md"), false));
+
+        // A file in an ignored directory is included again, and found by the walk.
+        fs::create_dir_all(root.join("target/release")).unwrap();
+        fs::write(root.join("target/release/app"), "").unwrap();
+        fs::write(root.join("target/release/app.d"), "").unwrap();
+        let rules = Rules { include: vec!["target/release/app".into()], exclude: vec!["src/".into()] };
+        let matcher = Arc::new(Matcher::new(root, &rules).unwrap());
+        assert!(matcher.is_ignored(&root.join("src/main.rs"), false));
+        // The directories no glob could match beneath are not walked.
+        assert!(matcher.is_ignored(&root.join("node_modules"), true));
+        assert!(!matcher.is_ignored(&root.join("target/release"), true));
+        let files: Vec<PathBuf> =
+            utils::walk(root, &matcher).unwrap().into_iter().map(|e| e.into_path()).filter(|p| p.is_file()).collect();
+        assert_eq!(files, vec![root.join("target/release/app")]);
+    }
+}
@@ -0,0 +1,400 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+use std::path::Path;
+
+use serde_yaml::Value as Yaml;
+use toml::Table;
+
+use crate::errors::{Errors, Result};
+use crate::ops::detector::{services, table, DEFAULT_BUILDER};
+
+/// The compose keys translated into the character, the others are reported.
+const COMPOSE_KEYS: [&str; 8] =
+    ["image", "build", "ports", "expose", "environment", "command", "depends_on", "container_name"];
+
+/// A character translated from a service of an existing configuration.
+#[derive(Debug, Default)]
+pub struct Imported {
+    /// The name of the character
+    pub name: String,
+    /// The build and deploy fields of the manifest
+    pub manifest: Table,
+    /// The names of the characters it depends on
+    pub partners: Vec<String>,
+}
+
+/// The characters translated from an existing configuration.
+#[derive(Debug, Default)]
+pub struct Import {
+    /// The translated characters
+    pub characters: Vec<Imported>,
+    /// What could not be mapped into the characters
+    pub unmapped: Vec<String>,
+}
+
+/// Translate a docker-compose file, Procfile or skaffold.yaml into characters.
+pub fn import(path: &Path) -> Result<Import> {
+    let content = fs::read_to_string(path).map_err(|e| Errors::FailedLoadImport(path.to_path_buf(), e))?;
+    let name = path.file_name().and_then(|n| n.to_str()).unwrap_or_default();
+
+    if name == "Procfile" {
+        return Ok(procfile(&content));
+    }
+    if name.starts_with("skaffold") {
+        return skaffold(&serde_yaml::from_str(&content).map_err(Errors::YamlDeserializeError)?);
+    }
+    if name.contains("compose") {
+        return compose(&serde_yaml::from_str(&content).map_err(Errors::YamlDeserializeError)?);
+    }
+
+    Err(Errors::UnsupportedImport(path.to_path_buf()))
+}
+
+/// Translate every compose service into a character, `depends_on` becomes its partners.
+fn compose(document: &Yaml) -> Result<Import> {
+    let mut import = Import::default();
+    let services = document
+        .get("services")
+        .and_then(Yaml::as_mapping)
+        .ok_or_else(|| Errors::InvalidImport("no services found in the compose file".into()))?;
+
+    for (key, service) in services {
+        let name = key.as_str().unwrap_or_default().to_string();
+        let mut manifest = Table::new();
+
+        match service.get("build") {
+            Some(Yaml::String(context)) => dockerfile(&mut manifest, context, "Dockerfile"),
+            Some(build) => dockerfile(
+                &mut manifest,
+                build.get("context").and_then(Yaml::as_str).unwrap_or("."),
+                build.get("dockerfile").and_then(Yaml::as_str).unwrap_or("Dockerfile"),
+            ),
+            None => {}
+        }
+        if let Some(image) = service.get("image").and_then(Yaml::as_str) {
+            table(&mut manifest, "deploy").insert("image".into(), image.into());
+        }
+
+        // Only the container ports matter, the host ports are chosen by `amp port-forward`.
+        let mut ports: Vec<u16> = vec![];
+        let declared = ["ports", "expose"].iter().filter_map(|key| service.get(key).and_then(Yaml::as_sequence));
+        for port in declared.flatten() {
+            match container_ports(port) {
+                Some(expanded) => ports.extend(expanded),
+                None => {
+                    let port = serde_yaml::to_string(port).unwrap_or_default();
+                    import.unmapped.push(format!("{name}: port `{}` is not supported", port.trim()));
+                }
+            }
+        }
+        services(&mut manifest, &ports);
+
+        if let Some(command) = service.get("command").and_then(command) {
+            table(&mut manifest, "deploy").insert("command".into(), command.into());
+        }
+
+        let env = environment(service.get("environment"));
+        if !env.is_empty() {
+            table(&mut manifest, "deploy").insert("env".into(), env.into());
+        }
+
+        let mut partners = vec![];
+        let depends_on: Vec<String> = match service.get("depends_on") {
+            Some(Yaml::Sequence(names)) => names.iter().filter_map(Yaml::as_str).map(String::from).collect(),
+            Some(Yaml::Mapping(names)) => names.keys().filter_map(Yaml::as_str).map(String::from).collect(),
+            _ => vec![],
+        };
+        for partner in depends_on {
+            if !services.contains_key(partner.as_str()) {
+                import.unmapped.push(format!("{name}: depends_on `{partner}` is not a service of the file"));
+                continue;
+            }
+            partners.push(partner);
+        }
+
+        if let Some(keys) = service.as_mapping().map(|m| m.keys()) {
+            for key in keys.filter_map(Yaml::as_str).filter(|k| !COMPOSE_KEYS.contains(k)) {
+                import.unmapped.push(format!("{name}: `{key}` is not supported"));
+            }
+        }
+
+        import.characters.push(Imported { name, manifest, partners });
+    }
+
+    for key in ["volumes", "networks", "secrets", "configs"] {
+        if document.get(key).is_some() {
+            import.unmapped.push(format!("top-level `{key}` is not supported"));
+        }
+    }
+
+    Ok(import)
+}
+
+/// Translate every process of the Procfile into a character built with buildpacks.
+fn procfile(content: &str) -> Import {
+    let mut import = Import::default();
+
+    for line in content.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        let Some((name, command)) = line.split_once(':') else {
+            import.unmapped.push(format!("malformed line `{line}`"));
+            continue;
+        };
+        let (name, command) = (name.trim(), command.trim());
+        if name == "release" {
+            import.unmapped.push(format!("{name}: release phase commands are not supported"));
+            continue;
+        }
+
+        let mut manifest = Table::new();
+        let mut buildpacks = Table::new();
+        buildpacks.insert("builder".into(), DEFAULT_BUILDER.into());
+        table(&mut manifest, "build").insert("buildpacks".into(), buildpacks.into());
+        table(&mut manifest, "deploy").insert("command".into(), command.into());
+        if name == "web" {
+            services(&mut manifest, &[8080]);
+            table(table(&mut manifest, "deploy"), "env").insert("PORT".into(), "8080".into());
+        }
+
+        import.characters.push(Imported { name: name.to_string(), manifest, partners: vec![] });
+    }
+
+    import
+}
+
+/// Translate every artifact of the skaffold build into a character.
+fn skaffold(document: &Yaml) -> Result<Import> {
+    let mut import = Import::default();
+    let artifacts = document
+        .get("build")
+        .and_then(|b| b.get("artifacts"))
+        .and_then(Yaml::as_sequence)
+        .ok_or_else(|| Errors::InvalidImport("no build artifacts found in the skaffold file".into()))?;
+
+    for artifact in artifacts {
+        let Some(image) = artifact.get("image").and_then(Yaml::as_str) else {
+            import.unmapped.push("an artifact without image is skipped".into());
+            continue;
+        };
+        let name = image.rsplit('/').next().unwrap_or(image).to_string();
+        let context = artifact.get("context").and_then(Yaml::as_str).unwrap_or(".");
+        let mut manifest = Table::new();
+
+        match artifact.get("buildpacks") {
+            Some(buildpacks) => {
+                let mut config = Table::new();
+                config.insert("context".into(), context.into());
+                let builder = buildpacks.get("builder").and_then(Yaml::as_str).unwrap_or(DEFAULT_BUILDER);
+                config.insert("builder".into(), builder.into());
+                table(&mut manifest, "build").insert("buildpacks".into(), config.into());
+            }
+            None => {
+                let docker = artifact.get("docker");
+                let file = docker.and_then(|d| d.get("dockerfile")).and_then(Yaml::as_str);
+                dockerfile(&mut manifest, context, file.unwrap_or("Dockerfile"));
+            }
+        }
+        for key in ["sync", "requires", "hooks", "custom", "bazel", "jib", "ko"] {
+            if artifact.get(key).is_some() {
+                import.unmapped.push(format!("{name}: `{key}` is not supported"));
+            }
+        }
+
+        import.characters.push(Imported { name, manifest, partners: vec![] });
+    }
+
+    let forwards = document.get("portForward").and_then(Yaml::as_sequence).map(Vec::len).unwrap_or(0);
+    if forwards > 0 {
+        import.unmapped.push(format!("{forwards} portForward entries, use `amp dev --forward` instead"));
+    }
+    for key in ["deploy", "manifests", "profiles", "test", "verify"] {
+        if document.get(key).is_some() {
+            import.unmapped.push(format!("`{key}` is not supported, the deployment is managed by Amphitheatre"));
+        }
+    }
+
+    Ok(import)
+}
+
+fn dockerfile(manifest: &mut Table, context: &str, dockerfile: &str) {
+    let mut config = Table::new();
+    config.insert("context".into(), context.into());
+    config.insert("dockerfile".into(), dockerfile.into());
+    table(manifest, "build").insert("dockerfile".into(), config.into());
+}
+
+/// Get the container ports from the short (`"8080:80/tcp"`) or long (`target: 80`) syntax,
+/// the ranges (`"8000-8010:8000-8010"`) are expanded.
+fn container_ports(port: &Yaml) -> Option<Vec<u16>> {
+    match port {
+        Yaml::Number(n) => n.as_u64().and_then(|n| u16::try_from(n).ok()).map(|n| vec![n]),
+        Yaml::String(s) => {
+            let port = s.rsplit(':').next()?.split('/').next()?;
+            match port.split_once('-') {
+                Some((start, end)) => {
+                    let (start, end): (u16, u16) = (start.parse().ok()?, end.parse().ok()?);
+                    (start <= end).then(|| (start..=end).collect())
+                }
+                None => port.parse().ok().map(|port| vec![port]),
+            }
+        }
+        Yaml::Mapping(_) => port.get("target").and_then(container_ports),
+        _ => None,
+    }
+}
+
+fn command(command: &Yaml) -> Option<String> {
+    match command {
+        Yaml::String(s) => Some(s.clone()),
+        Yaml::Sequence(args) => Some(args.iter().filter_map(Yaml::as_str).collect::<Vec<_>>().join(" ")),
+        _ => None,
+    }
+}
+
+/// Get the variables from the list (`KEY=VALUE`) or map syntax of the environment.
+fn environment(env: Option<&Yaml>) -> Table {
+    let mut vars = Table::new();
+    match env {
+        Some(Yaml::Sequence(pairs)) => {
+            for pair in pairs.iter().filter_map(Yaml::as_str) {
+                let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+                vars.insert(key.into(), value.into());
+            }
+        }
+        Some(Yaml::Mapping(map)) => {
+            for (key, value) in map {
+                let value = match value {
+                    Yaml::String(s) => s.clone(),
+                    Yaml::Null => String::new(),
+                    other => serde_yaml::to_string(other).unwrap_or_default().trim().to_string(),
+                };
+                vars.insert(key.as_str().unwrap_or_default().into(), value.into());
+            }
+        }
+        _ => {}
+    }
+    vars
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_import_compose() {
+        let content = r#"
+services:
+  web:
+    build: ./web
+    ports: ["8080:80", "443"]
+    environment:
+      - MODE=prod
+    depends_on: [db, cache]
+    volumes: ["./data:/data"]
+  db:
+    image: postgres:16
+    environment:
+      POSTGRES_PASSWORD: secret
+"#;
+        let import = compose(&serde_yaml::from_str(content).unwrap()).unwrap();
+        let web = &import.characters[0].manifest;
+
+        assert_eq!(import.characters[0].name, "web");
+        assert_eq!(web["build"]["dockerfile"]["context"].as_str(), Some("./web"));
+        assert_eq!(web["deploy"]["services"][0]["ports"][0]["port"].as_integer(), Some(80));
+        assert_eq!(web["deploy"]["services"][0]["ports"][1]["port"].as_integer(), Some(443));
+        assert_eq!(web["deploy"]["env"]["MODE"].as_str(), Some("prod"));
+        assert_eq!(import.characters[0].partners, vec!["db"]);
+        assert_eq!(import.characters[1].manifest["deploy"]["image"].as_str(), Some("postgres:16"));
+        assert_eq!(
+            import.unmapped,
+            vec!["web: depends_on `cache` is not a service of the file", "web: `volumes` is not supported"]
+        );
+    }
+
+    #[test]
+    fn test_import_port_ranges() {
+        let content = r#"
+services:
+  api:
+    image: api
+    ports: ["8000-8002:8000-8002", "9000-8999", "http"]
+"#;
+        let import = compose(&serde_yaml::from_str(content).unwrap()).unwrap();
+        let ports = &import.characters[0].manifest["deploy"]["services"][0]["ports"];
+
+        let ports: Vec<i64> = ports.as_array().unwrap().iter().filter_map(|p| p["port"].as_integer()).collect();
+        assert_eq!(ports, vec![8000, 8001, 8002]);
+        assert_eq!(
+            import.unmapped,
+            vec!["api: port `9000-8999` is not supported", "api: port `http` is not supported"]
+        );
+    }
+
+    #[test]
+    fn test_import_procfile() {
+        let content =
+            "# processes\nweb: bundle exec rails server\nworker: bundle exec sidekiq\nrelease: rake db:migrate\n";
+        let import = procfile(content);
+
+        assert_eq!(import.characters.len(), 2);
+        let web = &import.characters[0].manifest;
+        assert_eq!(import.characters[0].name, "web");
+        assert_eq!(web["build"]["buildpacks"]["builder"].as_str(), Some(DEFAULT_BUILDER));
+        assert_eq!(web["deploy"]["command"].as_str(), Some("bundle exec rails server"));
+        assert_eq!(web["deploy"]["env"]["PORT"].as_str(), Some("8080"));
+        assert_eq!(web["deploy"]["services"][0]["ports"][0]["port"].as_integer(), Some(8080));
+        assert!(import.characters[1].manifest["deploy"].get("services").is_none());
+        assert_eq!(import.unmapped, vec!["release: release phase commands are not supported"]);
+    }
+
+    #[test]
+    fn test_import_skaffold() {
+        let content = r#"
+build:
+  artifacts:
+    - image: gcr.io/x/api
+      context: api
+      docker:
+        dockerfile: Dockerfile.dev
+    - image: web
+      buildpacks:
+        builder: paketobuildpacks/builder:base
+      sync:
+        manual: []
+portForward:
+  - resourceName: api
+    port: 8080
+deploy:
+  kubectl: {}
+"#;
+        let import = skaffold(&serde_yaml::from_str(content).unwrap()).unwrap();
+
+        let api = &import.characters[0].manifest;
+        assert_eq!(import.characters[0].name, "api");
+        assert_eq!(api["build"]["dockerfile"]["context"].as_str(), Some("api"));
+        assert_eq!(api["build"]["dockerfile"]["dockerfile"].as_str(), Some("Dockerfile.dev"));
+        let web = &import.characters[1].manifest;
+        assert_eq!(web["build"]["buildpacks"]["builder"].as_str(), Some("paketobuildpacks/builder:base"));
+        assert_eq!(
+            import.unmapped,
+            vec![
+                "web: `sync` is not supported",
+                "1 portForward entries, use `amp dev --forward` instead",
+                "`deploy` is not supported, the deployment is managed by Amphitheatre",
+            ]
+        );
+    }
+}
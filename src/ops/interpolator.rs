@@ -0,0 +1,150 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use amp_common::schema::Character;
+use toml::Value;
+use tracing::{debug, warn};
+
+use crate::errors::{Errors, Result};
+
+/// Resolve the `${VAR}`, `${VAR:-default}` and `${VAR-default}` variables in the string fields
+/// of the character, undefined variables are replaced by an empty string, or fail in strict mode.
+/// As in the shell, `:-` applies the default to the undefined and empty variables, and `-` only
+/// to the undefined ones.
+pub fn resolve(character: &mut Character, workspace: &Path, strict: bool) -> Result<()> {
+    let mut value = Value::try_from(&*character).map_err(Errors::TomlSerializeError)?;
+    let mut variables = Variables::new(workspace);
+    walk(&mut value, &mut |s| expand(s, &mut |name| variables.get(name), strict))?;
+    *character = value.try_into().map_err(Errors::TomlDeserializeError)?;
+
+    Ok(())
+}
+
+fn walk(value: &mut Value, f: &mut impl FnMut(&str) -> Result<String>) -> Result<()> {
+    match value {
+        Value::String(s) if s.contains('$') => *s = f(s)?,
+        Value::Array(values) => values.iter_mut().try_for_each(|v| walk(v, f))?,
+        Value::Table(table) => table.values_mut().try_for_each(|v| walk(v, f))?,
+        _ => {}
+    }
+    Ok(())
+}
+
+/// Expand the variables in the string, `$${` escapes a literal `${`.
+pub fn expand(s: &str, lookup: &mut impl FnMut(&str) -> Option<String>, strict: bool) -> Result<String> {
+    let mut result = String::with_capacity(s.len());
+    let mut rest = s;
+
+    while let Some(start) = rest.find('$') {
+        result.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        if rest.starts_with("$${") {
+            result.push_str("${");
+            rest = &rest[3..];
+            continue;
+        }
+        if !rest.starts_with("${") {
+            result.push('$');
+            rest = &rest[1..];
+            continue;
+        }
+
+        let end = rest.find('}').ok_or_else(|| Errors::InvalidInterpolation(s.to_string()))?;
+        let expression = &rest[2..end];
+        let (name, default, empty) = match expression.split_once(":-") {
+            Some((name, default)) => (name, Some(default), true),
+            None => match expression.split_once('-') {
+                Some((name, default)) => (name, Some(default), false),
+                None => (expression, None, false),
+            },
+        };
+        if name.is_empty() {
+            return Err(Errors::InvalidInterpolation(s.to_string()));
+        }
+
+        // the empty value is the same as undefined when the default is given with `:-`.
+        let value = lookup(name).filter(|value| !(empty && value.is_empty()));
+        match (value, default) {
+            (Some(value), _) => result.push_str(&value),
+            (None, Some(default)) => result.push_str(default),
+            (None, None) if strict => return Err(Errors::UndefinedVariable(name.to_string())),
+            (None, None) => warn!("The variable `{}` is not defined, replaced with an empty string", name),
+        }
+        rest = &rest[end + 1..];
+    }
+    result.push_str(rest);
+
+    Ok(result)
+}
+
+/// The variables from the environment and the built-ins, the built-ins are only
+/// computed when they are used.
+struct Variables {
+    workspace: PathBuf,
+    builtins: HashMap<String, Option<String>>,
+}
+
+impl Variables {
+    fn new(workspace: &Path) -> Self {
+        Self { workspace: workspace.to_path_buf(), builtins: HashMap::new() }
+    }
+
+    fn get(&mut self, name: &str) -> Option<String> {
+        let builtin = match name {
+            "git.sha" => vec!["rev-parse", "HEAD"],
+            "git.branch" => vec!["rev-parse", "--abbrev-ref", "HEAD"],
+            "user" => {
+                return std::env::var("USER").or_else(|_| std::env::var("USERNAME")).ok();
+            }
+            _ => return std::env::var(name).ok(),
+        };
+
+        let workspace = &self.workspace;
+        self.builtins.entry(name.to_string()).or_insert_with(|| git(workspace, &builtin)).clone()
+    }
+}
+
+fn git(workspace: &Path, args: &[&str]) -> Option<String> {
+    let output = Command::new("git").args(args).current_dir(workspace).output().ok()?;
+    if !output.status.success() {
+        debug!("Failed to run git {:?}: {}", args, String::from_utf8_lossy(&output.stderr));
+        return None;
+    }
+    Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_expand_variables() {
+        let vars = HashMap::from([("TAG", "v1"), ("EMPTY", "")]);
+        let mut lookup = |name: &str| vars.get(name).map(|v| v.to_string());
+
+        assert_eq!(expand("app:${TAG}", &mut lookup, true).unwrap(), "app:v1");
+        assert_eq!(expand("${EMPTY:-dev}-${MISSING:-x}", &mut lookup, true).unwrap(), "dev-x");
+        assert_eq!(expand("${EMPTY-dev}-${MISSING-x}-${TAG-x}", &mut lookup, true).unwrap(), "-x-v1");
+        assert_eq!(expand("a${EMPTY}b", &mut lookup, true).unwrap(), "ab");
+        assert_eq!(expand("$$HOME $${TAG}", &mut lookup, true).unwrap(), "$$HOME ${TAG}");
+        assert_eq!(expand("a${MISSING}b", &mut lookup, false).unwrap(), "ab");
+        assert!(matches!(expand("${MISSING}", &mut lookup, true), Err(Errors::UndefinedVariable(_))));
+        assert!(matches!(expand("${TAG", &mut lookup, true), Err(Errors::InvalidInterpolation(_))));
+    }
+}
@@ -12,7 +12,21 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+pub mod artifacts;
 pub mod cleaner;
+pub mod debugger;
+pub mod detector;
+pub mod diagnosis;
+pub mod differ;
+pub mod env;
+pub mod formatter;
+pub mod forwarder;
+pub mod ignorer;
+pub mod importer;
+pub mod interpolator;
 pub mod logger;
 pub mod pipeline;
+pub mod terminal;
+pub mod tester;
+pub mod validator;
 pub mod watcher;
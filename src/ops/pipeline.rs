@@ -12,18 +12,24 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
 use std::sync::Arc;
 
 use amp_client::playbooks::{PlaybookPayload, Playbooks};
-use amp_common::filesystem::Finder;
 use amp_common::resource::{CharacterSpec, PlaybookSpec, Preface};
+use inquire::Confirm;
 use tokio::time::{sleep, Duration};
-use tracing::{debug, error, info};
+use tracing::{debug, error, info, warn};
 
-use crate::context::Context;
+use crate::context::{Context, Session};
 use crate::errors::{Errors, Result};
-use crate::ops::{cleaner, logger, watcher};
+use crate::manifest;
+use crate::ops::forwarder::{self, Mapping, Tunnel};
+use crate::ops::ignorer::Matcher;
+use crate::ops::watcher::Trigger;
+use crate::ops::{cleaner, env, interpolator, logger, validator, watcher};
+use crate::state::State;
 use crate::utils;
 
 /// The options for the pipeline.
@@ -36,6 +42,12 @@ pub struct Options {
     pub live: bool,
     /// Exit after one sync with live mode
     pub once: bool,
+    /// Watch file changes and sync them automatically
+    pub sync: bool,
+    /// Forward local ports to the lead character
+    pub forwards: Vec<Mapping>,
+    /// How the watcher detects and syncs the changes
+    pub watch: watcher::Settings,
 }
 
 /// Create a playbook from the remote git repository.
@@ -64,24 +76,213 @@ pub async fn fetch(ctx: &Context, name: &str) -> Result<PlaybookSpec> {
     .await
 }
 
+/// Locate the manifest file, find it in current or parent directories if not specified.
+pub fn locate(filename: &Option<PathBuf>) -> Result<PathBuf> {
+    match filename {
+        Some(path) => Ok(path.clone()),
+        None => manifest::find(&std::env::current_dir().map_err(Errors::FailedReadManifest)?),
+    }
+}
+
+/// Locate the manifest file, or fetch it into the local cache if it's a URL.
+pub async fn source(session: &Session, filename: &Option<PathBuf>) -> Result<PathBuf> {
+    match filename {
+        Some(url) if manifest::is_remote(url) => {
+            *session.remote.write().await = true;
+            manifest::fetch(&url.to_string_lossy()).await
+        }
+        _ => locate(filename),
+    }
+}
+
+/// Load the character from the manifest file into the session.
+pub async fn prepare(session: &Session, filename: &Option<PathBuf>) -> Result<()> {
+    session.load(&source(session, filename).await?).await
+}
+
+/// Resume the playbook left behind in the workspace by a previous session,
+/// returns None if there is nothing to resume or the user declined it.
+/// The environment variables given on the command line are applied to it.
+pub async fn resume(
+    ctx: &Context,
+    filename: &Option<PathBuf>,
+    assume_yes: bool,
+    once: bool,
+    vars: &HashMap<String, String>,
+) -> Result<Option<PlaybookSpec>> {
+    prepare(&ctx.session, filename).await?;
+
+    // There is no workspace for the manifest fetched from a URL.
+    if *ctx.session.remote.read().await {
+        return Ok(None);
+    }
+
+    let workspace = ctx.session.workspace.read().await.clone().unwrap();
+    let state = match State::load(&workspace)? {
+        Some(state) => state,
+        None => return Ok(None),
+    };
+
+    // The playbook was created on another cluster, leave it alone.
+    if state.server != ctx.cluster.read().await.server {
+        debug!("The session state belongs to another context: {:?}", state);
+        return Ok(None);
+    }
+
+    // The playbook may have been deleted since the session was left behind.
+    let mut playbook = match ctx.client.playbooks().get(&state.playbook).await {
+        Ok(playbook) => playbook,
+        Err(err) => {
+            debug!("The playbook {} is not available anymore: {:?}", state.playbook, err);
+            State::remove(&workspace)?;
+            return Ok(None);
+        }
+    };
+
+    let message = format!("Found playbook #{} left behind by a previous session, reuse it?", playbook.id);
+    if !assume_yes && !Confirm::new(&message).with_default(true).prompt().map_err(Errors::InquireError)? {
+        warn!("The playbook {} is kept on the server, use `amp clean {}` to delete it", playbook.id, playbook.id);
+        return Ok(None);
+    }
+
+    if !vars.is_empty() {
+        info!("Updating the playbook {} with the given environment variables", playbook.id);
+        let character = spec(&ctx.session, once, vars).await?;
+        let payload = payload(&character);
+        playbook = ctx.client.playbooks().update(&playbook.id, payload).await.map_err(Errors::FailedUpdatePlaybook)?;
+    }
+
+    info!("Resuming the playbook {}", playbook.id);
+    Ok(Some(playbook))
+}
+
+/// Get the playbook of the current session, which is persisted in the workspace.
+pub async fn current(ctx: &Context) -> Result<PlaybookSpec> {
+    prepare(&ctx.session, &None).await?;
+
+    let workspace = ctx.session.workspace.read().await.clone().unwrap();
+    let state = State::load(&workspace)?.ok_or(Errors::NotFoundSession)?;
+    let playbook = ctx.client.playbooks().get(&state.playbook).await.map_err(Errors::ClientError)?;
+    ctx.session.playbook.write().await.replace(playbook.clone());
+
+    Ok(playbook)
+}
+
 /// Create a playbook from the local manifest file.
-pub async fn load(ctx: &Context, filename: &Option<PathBuf>, once: bool) -> Result<PlaybookSpec> {
-    // load the character from the local character manifest.
-    let path = &filename.clone().unwrap_or(Finder::new().find().map_err(Errors::NotFoundManifest)?);
-    ctx.session.load(path).await?;
+pub async fn load(
+    ctx: &Context,
+    filename: &Option<PathBuf>,
+    once: bool,
+    vars: &HashMap<String, String>,
+) -> Result<PlaybookSpec> {
+    // validate and load the character from the character manifest.
+    let path = source(&ctx.session, filename).await?;
+    validator::check(&path, *ctx.session.remote.read().await)?;
+    ctx.session.load(&path).await?;
 
-    let manifest = ctx.session.character.read().await.clone().unwrap();
-    let character = CharacterSpec { live: true, once, ..CharacterSpec::from(&manifest) };
+    let character = spec(&ctx.session, once, vars).await?;
+    submit(ctx, &character).await
+}
 
-    create(
-        ctx.client.playbooks(),
-        PlaybookPayload {
-            title: "Untitled".to_string(),
-            description: "".to_string(),
-            preface: Preface::manifest(&character),
-        },
-    )
-    .await
+/// Build the character spec from the character loaded into the session.
+pub async fn spec(session: &Session, once: bool, vars: &HashMap<String, String>) -> Result<CharacterSpec> {
+    let mut manifest = session.character.read().await.clone().unwrap();
+
+    // resolve the variables in the manifest, e.g. `${git.sha}` for the image tag.
+    let workspace = session.workspace.read().await.clone().unwrap();
+    interpolator::resolve(&mut manifest, &workspace, *session.strict.read().await)?;
+    // the character from a URL has no local sources to sync.
+    let live = !*session.remote.read().await;
+    let mut character = CharacterSpec { live, once, ..CharacterSpec::from(&manifest) };
+
+    // the environment variables from command line take precedence over the manifest.
+    env::merge(&mut character, vars);
+
+    Ok(character)
+}
+
+/// Build the character spec deployed without the local sources, as `amp deploy` does.
+pub async fn deployable(session: &Session, vars: &HashMap<String, String>) -> Result<CharacterSpec> {
+    let mut character = spec(session, true, vars).await?;
+    character.live = false;
+
+    Ok(character)
+}
+
+/// Create a playbook from the given character spec.
+pub async fn submit(ctx: &Context, character: &CharacterSpec) -> Result<PlaybookSpec> {
+    create(ctx.client.playbooks(), payload(character)).await
+}
+
+/// Build the payload for creating a playbook from the given character spec.
+pub fn payload(character: &CharacterSpec) -> PlaybookPayload {
+    PlaybookPayload {
+        title: "Untitled".to_string(),
+        description: "".to_string(),
+        preface: Preface::manifest(character),
+    }
+}
+
+/// The description of the playbooks created by `amp deploy`.
+const DEPLOYED_DESCRIPTION: &str = "Deployed by amp deploy";
+
+/// Build the payload for deploying the given character spec, as `amp deploy` does,
+/// the playbook is titled with the name of the character.
+pub fn deployment(character: &CharacterSpec) -> PlaybookPayload {
+    PlaybookPayload {
+        title: character.meta.name.clone(),
+        description: DEPLOYED_DESCRIPTION.to_string(),
+        preface: Preface::manifest(character),
+    }
+}
+
+/// Whether the playbook was created by `amp deploy`, without the local sources.
+pub fn is_deployed(playbook: &PlaybookSpec) -> bool {
+    playbook.description.as_deref() == Some(DEPLOYED_DESCRIPTION)
+}
+
+/// Find the playbook deployed by `amp deploy` for the character, which is titled
+/// with its name, it's an error if several playbooks share the title.
+pub async fn deployed(ctx: &Context, name: &str) -> Result<Option<PlaybookSpec>> {
+    let playbooks = ctx.client.playbooks().list(None).await.map_err(Errors::ClientError)?;
+    let mut matched: Vec<PlaybookSpec> = playbooks.into_iter().filter(|p| p.title == name).collect();
+    if matched.len() > 1 {
+        let ids = matched.iter().map(|p| p.id.as_str()).collect::<Vec<_>>().join(", ");
+        return Err(Errors::AmbiguousPlaybook(name.to_string(), ids));
+    }
+
+    Ok(matched.pop())
+}
+
+/// Create or update the playbook with the given deployment payload, the playbook
+/// is the given one or found by its title. The existing playbook is deleted and
+/// created again if force is true.
+pub async fn deploy(
+    ctx: &Context,
+    payload: PlaybookPayload,
+    playbook: &Option<String>,
+    force: bool,
+) -> Result<PlaybookSpec> {
+    let existing = match playbook {
+        Some(id) => Some(ctx.client.playbooks().get(id).await.map_err(Errors::ClientError)?),
+        None => deployed(ctx, &payload.title).await?,
+    };
+
+    match existing {
+        Some(playbook) if force => {
+            warn!("Recreating the playbook {}, it might cause downtime", playbook.id);
+            let status = ctx.client.playbooks().delete(&playbook.id).await.map_err(Errors::ClientError)?;
+            if status != 204 {
+                return Err(Errors::FailedDeletePlaybook(playbook.id));
+            }
+            create(ctx.client.playbooks(), payload).await
+        }
+        Some(playbook) => {
+            info!("Updating the playbook {}...", playbook.id);
+            ctx.client.playbooks().update(&playbook.id, payload).await.map_err(Errors::FailedUpdatePlaybook)
+        }
+        None => create(ctx.client.playbooks(), payload).await,
+    }
 }
 
 /// Create a playbook from the given payload.
@@ -89,13 +290,19 @@ pub async fn create(client: Playbooks<'_>, payload: PlaybookPayload) -> Result<P
     let playbook = client.create(payload).await.map_err(Errors::FailedCreatePlaybook)?;
 
     info!("The playbook begins to create...");
-    debug!("The created playbook is:\n {:#?}", playbook);
+    debug!("The created playbook is:\n {:#}", env::redacted(&playbook)?);
 
     Ok(playbook)
 }
 
 /// Run a pipeline.
-pub async fn run(ctx: &Arc<Context>, playbook: PlaybookSpec, options: Options) -> Result<()> {
+pub async fn run(ctx: &Arc<Context>, playbook: PlaybookSpec, mut options: Options) -> Result<()> {
+    if *ctx.session.remote.read().await && (options.live || options.sync) {
+        warn!("The manifest is loaded from a URL, there is no local workspace to sync, live sync is disabled");
+        options.live = false;
+        options.sync = false;
+    }
+
     // wait playbook resolve finished.
     sleep(Duration::from_secs(10)).await;
 
@@ -105,27 +312,50 @@ pub async fn run(ctx: &Arc<Context>, playbook: PlaybookSpec, options: Options) -
     let pid = Arc::new(playbook.id.clone());
     let name = Arc::new(lead_name(&playbook).ok_or(Errors::InvalidCharacter)?);
 
+    // The initial sync and the watcher agree on the files to sync.
+    let workspace = ctx.session.workspace.read().await.clone();
+    let manifest = ctx.session.manifest.read().await.clone();
+    let matcher = matcher(workspace.as_deref(), manifest.as_deref(), &options)?;
+
+    // Persist the session state, so the playbook can be resumed after a crash,
+    // the playbooks of the one-off runs are never resumed.
+    if let (true, false, Some(workspace)) = (options.live, options.once, &workspace) {
+        let server = ctx.cluster.read().await.server.clone();
+        let manual = options.sync && options.watch.trigger == Trigger::Manual;
+        State { manual, ..State::new(&server, &pid, &name) }.save(workspace)?;
+    }
+
     // Initial sync the full sources into the server.
-    if options.live {
+    if let (true, Some(workspace), Some(matcher)) = (options.live, &workspace, &matcher) {
         info!("Syncing the full sources into the server...");
-        let workspace = ctx.session.workspace.read().await.clone().unwrap();
-        utils::upload(&ctx.client.actors(), &pid, &name, &workspace).await?;
+        utils::upload(&ctx.client.actors(), &pid, &name, workspace, matcher).await?;
     }
 
     // Watch file changes and sync the changed files.
-    if !options.once {
+    if let (false, true, Some(workspace), Some(matcher)) = (options.once, options.sync, workspace, matcher) {
         let client1 = ctx.client.clone();
         let pid1 = pid.clone();
         let name1 = name.clone();
-        let workspace = ctx.session.workspace.read().await.clone().unwrap();
+        let settings = options.watch.clone();
 
         tokio::spawn(async move {
-            if let Err(err) = watcher::watch(&workspace, &client1, &pid1, &name1).await {
+            if let Err(err) = watcher::watch(&workspace, &client1, &pid1, &name1, matcher, &settings).await {
                 error!("The watcher is stopped: {:?}", err);
             }
         });
     }
 
+    // Forward the local ports to the lead character.
+    let cluster = ctx.cluster.read().await.clone();
+    for mapping in options.forwards.iter().copied() {
+        let tunnel = Tunnel::new(&cluster.server, cluster.token.clone(), &pid, &name, mapping.remote);
+        tokio::spawn(async move {
+            if let Err(err) = forwarder::forward(mapping, tunnel).await {
+                error!("The port forwarding {} is stopped: {:?}", mapping, err);
+            }
+        });
+    }
+
     info!("The playbook is running...");
 
     // Receive the log stream from the server.
@@ -145,8 +375,19 @@ pub async fn run(ctx: &Arc<Context>, playbook: PlaybookSpec, options: Options) -
     Ok(())
 }
 
+/// The matcher of the files to sync, shared by the initial sync and the watcher, it's
+/// None if nothing is synced, or there is no local workspace, e.g. for `amp run --git`.
+fn matcher(workspace: Option<&Path>, manifest: Option<&Path>, options: &Options) -> Result<Option<Arc<Matcher>>> {
+    match workspace {
+        Some(workspace) if options.live || (options.sync && !options.once) => {
+            Ok(Some(Arc::new(Matcher::load(workspace, manifest)?)))
+        }
+        _ => Ok(None),
+    }
+}
+
 /// get lead character name based on preface type.
-fn lead_name(playbook: &PlaybookSpec) -> Option<String> {
+pub fn lead_name(playbook: &PlaybookSpec) -> Option<String> {
     if playbook.preface.registry.is_some() || playbook.preface.manifest.is_some() {
         return playbook.preface.name.clone();
     }
@@ -162,3 +403,30 @@ fn lead_name(playbook: &PlaybookSpec) -> Option<String> {
 
     None
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+
+    #[test]
+    fn test_matcher_without_workspace() {
+        let options = |live, once, sync| Options {
+            cleanup: false,
+            tail: false,
+            live,
+            once,
+            sync,
+            forwards: vec![],
+            watch: Default::default(),
+        };
+
+        // `amp run --git` and `amp run --name` have no workspace to sync.
+        assert!(matcher(None, None, &options(true, true, true)).unwrap().is_none());
+
+        let dir = utils::workspace(&[("main.rs", "")]);
+        assert!(matcher(Some(dir.path()), None, &options(false, true, true)).unwrap().is_none());
+        assert!(matcher(Some(dir.path()), None, &options(true, true, false)).unwrap().is_some());
+        assert!(matcher(Some(dir.path()), None, &options(false, false, true)).unwrap().is_some());
+    }
+}
@@ -0,0 +1,219 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::io::{Read, Write};
+
+use amp_common::config::Cluster;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use tokio::sync::mpsc;
+use tracing::{debug, warn};
+
+use crate::errors::{Errors, Result};
+use crate::utils;
+
+/// The channels multiplexed over the exec stream, every frame is
+/// a channel byte, followed by a big-endian u32 length and the payload.
+pub const STDIN: u8 = 0;
+pub const STDOUT: u8 = 1;
+pub const STDERR: u8 = 2;
+pub const RESIZE: u8 = 3;
+pub const EXIT: u8 = 4;
+/// The largest frame accepted from the server.
+const MAX_FRAME_SIZE: usize = 1024 * 1024;
+
+/// The options for executing a command in an actor.
+pub struct Options {
+    /// The command and its arguments
+    pub command: Vec<String>,
+    /// Pass stdin to the command
+    pub stdin: bool,
+    /// Allocate a TTY for the command
+    pub tty: bool,
+}
+
+/// Execute the command in the actor and stream stdin, stdout and stderr
+/// until the command exits, returns the exit code of the command.
+pub async fn exec(cluster: &Cluster, pid: &str, name: &str, options: &Options) -> Result<i32> {
+    execute(cluster, pid, name, options, &mut std::io::stdout(), &mut std::io::stderr()).await
+}
+
+/// Execute the command in the actor, and write its output into the given writers.
+pub async fn execute<O: Write, E: Write>(
+    cluster: &Cluster,
+    pid: &str,
+    name: &str,
+    options: &Options,
+    stdout: &mut O,
+    stderr: &mut E,
+) -> Result<i32> {
+    let url = format!("{}/v1/actors/{}/{}/exec", cluster.server.trim_end_matches('/'), pid, name);
+    let mut query: Vec<(&str, String)> = options.command.iter().map(|c| ("command", c.clone())).collect();
+    query.push(("stdin", options.stdin.to_string()));
+    query.push(("tty", options.tty.to_string()));
+
+    let req = reqwest::Client::new().get(url).query(&query);
+    let stream = utils::upgrade(req, &cluster.token, "exec").await?;
+    let (mut reader, mut writer) = tokio::io::split(stream);
+
+    // Keep the terminal in raw mode while the TTY is attached.
+    let _guard = match options.tty {
+        true => Some(RawMode::enable()?),
+        false => None,
+    };
+
+    // All the frames to the server go through one writer.
+    let (tx, mut rx) = mpsc::channel::<(u8, Vec<u8>)>(32);
+    tokio::spawn(async move {
+        while let Some((channel, payload)) = rx.recv().await {
+            if let Err(err) = write_frame(&mut writer, channel, &payload).await {
+                warn!("Failed to send to the exec stream: {}", err);
+                break;
+            }
+        }
+    });
+
+    if options.tty {
+        if let Ok((cols, rows)) = crossterm::terminal::size() {
+            let _ = tx.send((RESIZE, resize_payload(cols, rows))).await;
+        }
+        tokio::spawn(watch_resize(tx.clone()));
+    }
+    if options.stdin {
+        forward_stdin(tx.clone());
+    }
+    drop(tx);
+
+    loop {
+        let (channel, payload) = match read_frame(&mut reader).await {
+            Ok(frame) => frame,
+            Err(err) => return Err(Errors::TunnelError(err)),
+        };
+
+        match channel {
+            STDOUT => stdout.write_all(&payload).and_then(|_| stdout.flush()).map_err(Errors::TunnelError)?,
+            STDERR => stderr.write_all(&payload).and_then(|_| stderr.flush()).map_err(Errors::TunnelError)?,
+            EXIT => {
+                let code = payload.get(..4).map(|b| i32::from_be_bytes([b[0], b[1], b[2], b[3]])).unwrap_or(1);
+                debug!("The command exited with code {}", code);
+                return Ok(code);
+            }
+            _ => warn!("Unknown channel {} from the exec stream", channel),
+        }
+    }
+}
+
+/// Read the stdin and send it to the server, an empty frame means EOF.
+/// The blocking read runs on a detached thread, so it doesn't hold the
+/// runtime from shutting down after the command exits.
+fn forward_stdin(tx: mpsc::Sender<(u8, Vec<u8>)>) {
+    std::thread::spawn(move || {
+        let mut stdin = std::io::stdin();
+        let mut buf = vec![0; 4096];
+
+        loop {
+            let n = stdin.read(&mut buf).unwrap_or(0);
+            if tx.blocking_send((STDIN, buf[..n].to_vec())).is_err() || n == 0 {
+                break;
+            }
+        }
+    });
+}
+
+/// Send the new terminal size to the server whenever the window is resized.
+#[cfg(unix)]
+async fn watch_resize(tx: mpsc::Sender<(u8, Vec<u8>)>) {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    let mut signals = match signal(SignalKind::window_change()) {
+        Ok(signals) => signals,
+        Err(err) => {
+            warn!("Failed to watch the terminal size: {}", err);
+            return;
+        }
+    };
+    while signals.recv().await.is_some() {
+        if let Ok((cols, rows)) = crossterm::terminal::size() {
+            if tx.send((RESIZE, resize_payload(cols, rows))).await.is_err() {
+                break;
+            }
+        }
+    }
+}
+
+#[cfg(not(unix))]
+async fn watch_resize(_tx: mpsc::Sender<(u8, Vec<u8>)>) {}
+
+fn resize_payload(cols: u16, rows: u16) -> Vec<u8> {
+    [cols.to_be_bytes(), rows.to_be_bytes()].concat()
+}
+
+/// Write a frame to the stream.
+pub async fn write_frame<W: AsyncWrite + Unpin>(writer: &mut W, channel: u8, payload: &[u8]) -> std::io::Result<()> {
+    let mut frame = Vec::with_capacity(payload.len() + 5);
+    frame.push(channel);
+    frame.extend_from_slice(&(payload.len() as u32).to_be_bytes());
+    frame.extend_from_slice(payload);
+
+    writer.write_all(&frame).await?;
+    writer.flush().await
+}
+
+/// Read a frame from the stream.
+pub async fn read_frame<R: AsyncRead + Unpin>(reader: &mut R) -> std::io::Result<(u8, Vec<u8>)> {
+    let channel = reader.read_u8().await?;
+    let len = reader.read_u32().await? as usize;
+    if len > MAX_FRAME_SIZE {
+        let message = format!("frame of {len} bytes exceeds the limit of {MAX_FRAME_SIZE} bytes");
+        return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, message));
+    }
+    let mut payload = vec![0; len];
+    reader.read_exact(&mut payload).await?;
+
+    Ok((channel, payload))
+}
+
+/// RawMode enables the raw mode of the terminal, and restores it when dropped.
+struct RawMode;
+
+impl RawMode {
+    fn enable() -> Result<Self> {
+        crossterm::terminal::enable_raw_mode().map_err(Errors::TunnelError)?;
+        Ok(RawMode)
+    }
+}
+
+impl Drop for RawMode {
+    fn drop(&mut self) {
+        let _ = crossterm::terminal::disable_raw_mode();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_frame_roundtrip() {
+        let mut buf = vec![];
+        write_frame(&mut buf, STDOUT, b"hello").await.unwrap();
+        write_frame(&mut buf, EXIT, &0i32.to_be_bytes()).await.unwrap();
+
+        let mut reader = buf.as_slice();
+        assert_eq!(read_frame(&mut reader).await.unwrap(), (STDOUT, b"hello".to_vec()));
+        assert_eq!(read_frame(&mut reader).await.unwrap(), (EXIT, vec![0, 0, 0, 0]));
+
+        let oversized = [&[STDOUT][..], &(MAX_FRAME_SIZE as u32 + 1).to_be_bytes()].concat();
+        assert!(read_frame(&mut oversized.as_slice()).await.is_err());
+    }
+}
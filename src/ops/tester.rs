@@ -0,0 +1,251 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use amp_common::config::Cluster;
+use clap::ValueEnum;
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize};
+use tokio::net::TcpListener;
+use tokio::process::Command;
+use tracing::{debug, info, warn};
+
+use crate::errors::{Errors, Result};
+use crate::manifest;
+use crate::ops::forwarder::{self, Mapping, Tunnel};
+use crate::ops::terminal::{self, Options};
+
+/// A test defined in the `[[tests]]` tables of the character manifest.
+#[derive(Deserialize, JsonSchema, Debug, Clone)]
+pub struct Test {
+    /// The name of the test
+    pub name: String,
+    /// The command to run inside the actor
+    pub command: Option<Vec<String>>,
+    /// The command to run locally, against the forwarded endpoint
+    pub local: Option<String>,
+    /// The port to forward for the local command, in the form of LOCAL:REMOTE or PORT
+    pub forward: Option<String>,
+}
+
+/// The result of a test.
+#[derive(Serialize, Debug, Clone)]
+pub struct Outcome {
+    pub name: String,
+    pub code: i32,
+    pub stdout: String,
+    pub stderr: String,
+    pub duration: f64,
+}
+
+impl Outcome {
+    pub fn passed(&self) -> bool {
+        self.code == 0
+    }
+}
+
+/// The formats of the test report.
+#[derive(Clone, Copy, Debug, PartialEq, ValueEnum)]
+pub enum ReportFormat {
+    Junit,
+    Json,
+}
+
+/// The key of the tests in the manifest.
+const TESTS: &str = "tests";
+/// The interval to probe whether the actor is ready.
+const PROBE_INTERVAL: Duration = Duration::from_secs(2);
+
+/// Load the tests from the character manifest file, in any format and composed
+/// with the manifests it extends and includes.
+pub fn load(path: &Path) -> Result<Vec<Test>> {
+    let (mut manifest, _) = manifest::compose(path)?;
+    match manifest.remove(TESTS) {
+        Some(tests) => tests.try_into().map_err(Errors::TomlDeserializeError),
+        None => Ok(vec![]),
+    }
+}
+
+/// Wait until the actor accepts commands, or the timeout expires.
+pub async fn wait(cluster: &Cluster, pid: &str, name: &str, timeout: Duration) -> Result<()> {
+    info!("Waiting for the character {} to be ready...", name);
+    let start = Instant::now();
+    let options = Options { command: vec!["true".into()], stdin: false, tty: false };
+
+    loop {
+        match terminal::execute(cluster, pid, name, &options, &mut std::io::sink(), &mut std::io::sink()).await {
+            Ok(_) => return Ok(()),
+            Err(err) if start.elapsed() < timeout => debug!("The character {} is not ready: {}", name, err),
+            Err(err) => return Err(Errors::NotReadyCharacter(name.to_string(), err.to_string())),
+        }
+        tokio::time::sleep(PROBE_INTERVAL).await;
+    }
+}
+
+/// Run the test against the lead character of the playbook.
+pub async fn run(cluster: &Cluster, pid: &str, name: &str, test: &Test) -> Result<Outcome> {
+    info!("Running test {}...", test.name);
+    let start = Instant::now();
+
+    let (code, stdout, stderr) = match (&test.command, &test.local) {
+        (Some(command), _) => {
+            let options = Options { command: command.clone(), stdin: false, tty: false };
+            let (mut stdout, mut stderr) = (vec![], vec![]);
+            let code = terminal::execute(cluster, pid, name, &options, &mut stdout, &mut stderr).await?;
+            (code, stdout, stderr)
+        }
+        (None, Some(local)) => run_local(cluster, pid, name, test, local).await?,
+        (None, None) => {
+            let message = format!("{}: either `command` or `local` is required", test.name);
+            return Err(Errors::InvalidTest(message));
+        }
+    };
+
+    Ok(Outcome {
+        name: test.name.clone(),
+        code,
+        stdout: String::from_utf8_lossy(&stdout).to_string(),
+        stderr: String::from_utf8_lossy(&stderr).to_string(),
+        duration: start.elapsed().as_secs_f64(),
+    })
+}
+
+async fn run_local(
+    cluster: &Cluster,
+    pid: &str,
+    name: &str,
+    test: &Test,
+    local: &str,
+) -> Result<(i32, Vec<u8>, Vec<u8>)> {
+    // Forward the port for the local command, and stop forwarding when it's done.
+    let forwarding = match &test.forward {
+        Some(forward) => {
+            let mapping: Mapping = forward.parse().map_err(|e| Errors::InvalidTest(format!("{}: {}", test.name, e)))?;
+            let tunnel = Tunnel::new(&cluster.server, cluster.token.clone(), pid, name, mapping.remote);
+            // The local port accepts connections once it's bound, before the command starts.
+            let listener = TcpListener::bind(("127.0.0.1", mapping.local)).await.map_err(Errors::FailedListenPort)?;
+            Some(tokio::spawn(forwarder::serve(listener, tunnel)))
+        }
+        None => None,
+    };
+
+    #[cfg(windows)]
+    let output = Command::new("cmd").arg("/C").arg(local).output().await;
+    #[cfg(not(windows))]
+    let output = Command::new("sh").arg("-c").arg(local).output().await;
+
+    if let Some(handle) = forwarding {
+        handle.abort();
+    }
+
+    let output = output.map_err(Errors::FailedRunTest)?;
+    let code = output.status.code().unwrap_or_else(|| {
+        warn!("The test {} is terminated by signal", test.name);
+        1
+    });
+
+    Ok((code, output.stdout, output.stderr))
+}
+
+/// Render the test report in the given format.
+pub fn report(suite: &str, outcomes: &[Outcome], format: ReportFormat) -> Result<String> {
+    match format {
+        ReportFormat::Json => serde_json::to_string_pretty(outcomes).map_err(Errors::JsonSerializeError),
+        ReportFormat::Junit => Ok(junit(suite, outcomes)),
+    }
+}
+
+fn junit(suite: &str, outcomes: &[Outcome]) -> String {
+    let failures = outcomes.iter().filter(|o| !o.passed()).count();
+    let time: f64 = outcomes.iter().map(|o| o.duration).sum();
+    let suite = escape(suite);
+
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<testsuites tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+        outcomes.len(),
+        failures,
+        time
+    ));
+    xml.push_str(&format!(
+        "  <testsuite name=\"{}\" tests=\"{}\" failures=\"{}\" time=\"{:.3}\">\n",
+        suite,
+        outcomes.len(),
+        failures,
+        time
+    ));
+    for outcome in outcomes {
+        xml.push_str(&format!(
+            "    <testcase name=\"{}\" classname=\"{}\" time=\"{:.3}\">\n",
+            escape(&outcome.name),
+            suite,
+            outcome.duration
+        ));
+        if !outcome.passed() {
+            xml.push_str(&format!("      <failure message=\"exit code {}\"/>\n", outcome.code));
+        }
+        xml.push_str(&format!("      <system-out>{}</system-out>\n", escape(&outcome.stdout)));
+        xml.push_str(&format!("      <system-err>{}</system-err>\n", escape(&outcome.stderr)));
+        xml.push_str("    </testcase>\n");
+    }
+    xml.push_str("  </testsuite>\n</testsuites>\n");
+
+    xml
+}
+
+/// Escape the text for XML, the control characters not allowed in XML 1.0 are dropped.
+fn escape(s: &str) -> String {
+    s.chars()
+        .filter(|c| !matches!(c, '\u{0}'..='\u{8}' | '\u{b}' | '\u{c}' | '\u{e}'..='\u{1f}' | '\u{fffe}' | '\u{ffff}'))
+        .collect::<String>()
+        .replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+
+    #[test]
+    fn test_load_tests() {
+        let dir = utils::workspace(&[
+            ("base.yaml", "tests:\n  - name: unit\n    command: [\"cargo\", \"test\"]\n"),
+            (".amp.yaml", "extends: base.yaml\ncharacter:\n  name: api\n"),
+        ]);
+
+        let tests = load(&dir.path().join(".amp.yaml")).unwrap();
+        assert_eq!(tests.len(), 1);
+        assert_eq!(tests[0].command, Some(vec!["cargo".to_string(), "test".to_string()]));
+    }
+
+    #[test]
+    fn test_junit_report() {
+        let outcomes = vec![
+            Outcome { name: "unit".into(), code: 0, stdout: "ok".into(), stderr: "".into(), duration: 0.5 },
+            Outcome { name: "smoke".into(), code: 7, stdout: "".into(), stderr: "<refused>".into(), duration: 0.25 },
+        ];
+
+        let xml = junit("api", &outcomes);
+        assert!(xml.contains("<testsuite name=\"api\" tests=\"2\" failures=\"1\" time=\"0.750\">"));
+        assert!(xml.contains("<failure message=\"exit code 7\"/>"));
+        assert!(xml.contains("<system-err>&lt;refused&gt;</system-err>"));
+
+        assert_eq!(escape("\u{1b}[31mred\u{1b}[0m\u{0}\tok\n"), "[31mred[0m\tok\n");
+    }
+}
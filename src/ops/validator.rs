@@ -0,0 +1,342 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+
+use amp_common::schema::Character;
+use colored::Colorize;
+use toml::{Table, Value};
+use tracing::{error, warn};
+
+use crate::errors::{Errors, Result};
+use crate::format::Format;
+use crate::manifest;
+
+/// The keys known in the manifest tables, besides the ones of a default character.
+const TOP_LEVEL_KEYS: [&str; 9] =
+    ["character", "build", "deploy", "partners", "sync", "tests", "extends", "include", "profiles"];
+const BUILD_KEYS: [&str; 5] = ["context", "dockerfile", "buildpacks", "env", "args"];
+const DEPLOY_KEYS: [&str; 6] = ["image", "command", "args", "env", "services", "resources"];
+const SYNC_KEYS: [&str; 2] = ["include", "exclude"];
+
+/// The severity of a problem, only errors make the manifest invalid.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+/// A problem found in the manifest, with the 1-based position it's found at.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Problem {
+    pub severity: Severity,
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl Problem {
+    fn error(position: (usize, usize), message: impl Into<String>) -> Self {
+        Self { severity: Severity::Error, line: position.0, column: position.1, message: message.into() }
+    }
+
+    fn warning(position: (usize, usize), message: impl Into<String>) -> Self {
+        Self { severity: Severity::Warning, line: position.0, column: position.1, message: message.into() }
+    }
+}
+
+impl Display for Problem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self.severity {
+            Severity::Error => "error".red(),
+            Severity::Warning => "warning".yellow(),
+        };
+        write!(f, "{}:{}: {}: {}", self.line, self.column, severity, self.message)
+    }
+}
+
+/// Validate the manifest, and fail if there is any error in it.
+pub fn check(path: &Path, remote: bool) -> Result<()> {
+    let problems = validate(path, remote)?;
+    for problem in &problems {
+        match problem.severity {
+            Severity::Error => error!("{}:{}", path.display(), problem),
+            Severity::Warning => warn!("{}:{}", path.display(), problem),
+        }
+    }
+
+    match problems.iter().filter(|p| p.severity == Severity::Error).count() {
+        0 => Ok(()),
+        n => Err(Errors::InvalidManifest(n)),
+    }
+}
+
+/// Validate the syntax, the keys and the semantics of the manifest file. The files
+/// it refers to are not checked for a remote manifest, which has no workspace.
+pub fn validate(path: &Path, remote: bool) -> Result<Vec<Problem>> {
+    let content = fs::read_to_string(path).map_err(Errors::FailedReadManifest)?;
+    let workspace = match remote {
+        true => None,
+        false => Some(path.parent().unwrap_or(Path::new("."))),
+    };
+
+    let format = Format::from_path(path);
+
+    let manifest: Table = match format.deserialize(&content) {
+        Ok(manifest) => manifest,
+        Err(err) => return Ok(vec![syntax(&content, err)]),
+    };
+
+    // The composed manifest must be a valid character, while the keys and the
+    // semantics are only checked in this file, where they can be located.
+    let mut problems = vec![];
+    if ["extends", "include", "profiles"].iter().any(|key| manifest.contains_key(*key)) {
+        if let Err(err) = manifest::load(path, &[]) {
+            problems.push(Problem::error((1, 1), err.to_string()));
+        }
+    } else if let Err(err) = format.deserialize::<Character>(&content) {
+        problems.push(syntax(&content, err));
+    }
+
+    // The keys are only located in TOML, the others are reported at the top of the file.
+    let content = match format {
+        Format::Toml => content,
+        Format::Json | Format::Yaml => String::new(),
+    };
+    problems.extend(unknown_keys(&content, &manifest));
+    problems.extend(semantics(&content, &manifest, workspace));
+    problems.sort_by_key(|p| (p.line, p.column));
+
+    Ok(problems)
+}
+
+/// Flag the keys not known by the schema, with a suggestion of the closest known key.
+fn unknown_keys(content: &str, manifest: &Table) -> Vec<Problem> {
+    // The keys of a default character are always known, whatever the schema names them.
+    let defaults = Value::try_from(Character::new("default")).ok();
+    let defaults = defaults.as_ref().and_then(Value::as_table);
+
+    let mut top: HashSet<&str> = TOP_LEVEL_KEYS.into_iter().collect();
+    top.extend(defaults.iter().flat_map(|t| t.keys().map(String::as_str)));
+
+    let mut problems = check_keys(content, &[], manifest, &top);
+    for (name, known) in [("build", &BUILD_KEYS[..]), ("deploy", &DEPLOY_KEYS[..]), ("sync", &SYNC_KEYS[..])] {
+        if let Some(table) = manifest.get(name).and_then(Value::as_table) {
+            let mut known: HashSet<&str> = known.iter().copied().collect();
+            known.extend(
+                defaults
+                    .and_then(|t| t.get(name))
+                    .and_then(Value::as_table)
+                    .iter()
+                    .flat_map(|t| t.keys().map(String::as_str)),
+            );
+            problems.extend(check_keys(content, &[name], table, &known));
+        }
+    }
+
+    problems
+}
+
+fn check_keys(content: &str, parents: &[&str], table: &Table, known: &HashSet<&str>) -> Vec<Problem> {
+    let mut problems = vec![];
+    for key in table.keys().filter(|k| !known.contains(k.as_str())) {
+        let mut path = parents.to_vec();
+        path.push(key.as_str());
+        let mut message = format!("unknown key `{}`", path.join("."));
+        if let Some(suggestion) = known.iter().filter(|k| distance(key, k) <= 2).min_by_key(|k| distance(key, k)) {
+            message.push_str(&format!(", did you mean `{suggestion}`?"));
+        }
+        problems.push(Problem::warning(position(content, &path), message));
+    }
+    problems
+}
+
+/// Check the problems the schema can not express.
+fn semantics(content: &str, manifest: &Table, workspace: Option<&Path>) -> Vec<Problem> {
+    let mut problems = vec![];
+
+    // Every port can only be exposed once.
+    let mut ports = HashSet::new();
+    let services = manifest.get("deploy").and_then(|d| d.get("services")).and_then(Value::as_array);
+    for port in services.into_iter().flatten().filter_map(|s| s.get("ports")?.as_array()).flatten() {
+        if let Some(port) = port.get("port").and_then(Value::as_integer) {
+            if !ports.insert(port) {
+                let position = find(content, "port", &port.to_string(), 2).unwrap_or((1, 1));
+                problems.push(Problem::error(position, format!("duplicate port {port} in `deploy.services`")));
+            }
+        }
+    }
+
+    // The Dockerfile must exist in the build context.
+    let dockerfile = manifest.get("build").and_then(|b| b.get("dockerfile")).and_then(Value::as_table);
+    if let (Some(build), Some(workspace)) = (dockerfile, workspace) {
+        let context = build.get("context").and_then(Value::as_str).unwrap_or(".");
+        let dockerfile = build.get("dockerfile").and_then(Value::as_str).unwrap_or("Dockerfile");
+        if !workspace.join(context).join(dockerfile).is_file() {
+            let position = position(content, &["build", "dockerfile", "dockerfile"]);
+            problems
+                .push(Problem::error(position, format!("Dockerfile `{dockerfile}` not found in context `{context}`")));
+        }
+    }
+
+    // The partners must point to a local manifest, a repository or the registry.
+    for (name, partner) in manifest.get("partners").and_then(Value::as_table).into_iter().flatten() {
+        let position = position(content, &["partners", name.as_str()]);
+        match partner.get("path").and_then(Value::as_str) {
+            Some(path) if workspace.is_some_and(|w| !w.join(path).exists()) => {
+                problems.push(Problem::error(position, format!("partner `{name}` points to missing path `{path}`")));
+            }
+            Some(_) => {}
+            None if partner.get("repository").is_none() && partner.get("registry").is_none() => {
+                problems.push(Problem::error(
+                    position,
+                    format!("partner `{name}` does not resolve, set one of `path`, `repository` or `registry`"),
+                ));
+            }
+            None => {}
+        }
+    }
+
+    problems
+}
+
+/// Turn the deserialization error into a problem at the position it's found.
+fn syntax(content: &str, err: Errors) -> Problem {
+    match err {
+        Errors::TomlDeserializeError(err) => Problem::error(span(content, &err), err.message()),
+        Errors::JsonDeserializeError(err) => Problem::error((err.line(), err.column()), err.to_string()),
+        Errors::YamlDeserializeError(err) => {
+            let position = err.location().map(|l| (l.line(), l.column())).unwrap_or((1, 1));
+            Problem::error(position, err.to_string())
+        }
+        err => Problem::error((1, 1), err.to_string()),
+    }
+}
+
+/// Get the line and column of the error span.
+fn span(content: &str, err: &toml::de::Error) -> (usize, usize) {
+    let offset = err.span().map(|s| s.start).unwrap_or(0).min(content.len());
+    let before = &content[..offset];
+    let line = before.matches('\n').count() + 1;
+    let column = before.rsplit('\n').next().map(|l| l.chars().count()).unwrap_or(0) + 1;
+    (line, column)
+}
+
+/// Find the position the dotted key is defined at, in a table header or as an assignment,
+/// falls back to the position of the closest parent.
+fn position(content: &str, path: &[&str]) -> (usize, usize) {
+    let mut table: Vec<&str> = vec![];
+    let mut found = (1, 1);
+    let mut depth = 0;
+
+    for (index, line) in content.lines().enumerate() {
+        let trimmed = line.trim_start();
+        let column = line.len() - trimmed.len() + 1;
+        if let Some(header) = trimmed.strip_prefix('[') {
+            table = header.trim_matches(|c| c == '[' || c == ']' || c == ' ').split('.').map(str::trim).collect();
+            let matched = table.iter().zip(path).take_while(|(a, b)| a == b).count();
+            if matched == table.len() && matched > depth {
+                (found, depth) = ((index + 1, column), matched);
+            }
+        } else if let Some((key, _)) = trimmed.split_once('=') {
+            let mut full = table.clone();
+            full.extend(key.trim().trim_matches('"').split('.').map(str::trim));
+            let matched = full.iter().zip(path).take_while(|(a, b)| a == b).count();
+            if matched == full.len() && matched > depth {
+                (found, depth) = ((index + 1, column), matched);
+            }
+        }
+    }
+
+    found
+}
+
+/// Find the nth line assigning the number to the whole key, returns the line
+/// and the column of the key.
+fn find(content: &str, key: &str, value: &str, nth: usize) -> Option<(usize, usize)> {
+    let is_key = |c: char| c.is_alphanumeric() || c == '_' || c == '-';
+    let mut seen = 0;
+    content.lines().enumerate().find_map(|(index, line)| {
+        let column = line.match_indices(key).map(|(i, _)| i).find(|&i| {
+            let before = line[..i].trim_end_matches('"');
+            if before.ends_with(is_key) {
+                return false;
+            }
+            let rest = line[i + key.len()..].trim_start_matches('"').trim_start();
+            let Some(rest) = rest.strip_prefix(['=', ':']) else {
+                return false;
+            };
+            match rest.trim_start().strip_prefix(value) {
+                Some(rest) => !rest.starts_with(|c: char| c.is_ascii_digit()),
+                None => false,
+            }
+        })?;
+        seen += 1;
+        (seen >= nth).then_some((index + 1, line[..column].chars().count() + 1))
+    })
+}
+
+/// The Levenshtein distance between two keys.
+fn distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut row: Vec<usize> = (0..=b.len()).collect();
+    for (i, ca) in a.chars().enumerate() {
+        let mut previous = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let current = row[j + 1];
+            row[j + 1] = (previous + usize::from(ca != *cb)).min(row[j] + 1).min(current + 1);
+            previous = current;
+        }
+    }
+    row[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+
+    #[test]
+    fn test_validate_manifest() {
+        let dir = utils::workspace(&[]);
+        let path = dir.path().join(".amp.toml");
+
+        fs::write(&path, "[character]\nname = \"demo\"\n\n[deploy\nimage = \"nginx\"\n").unwrap();
+        let problems = validate(&path, false).unwrap();
+        assert_eq!((problems[0].severity, problems[0].line), (Severity::Error, 4));
+
+        let content =
+            "[build.dockerfile]\ncontext = \".\"\ndockerfile = \"Dockerfile\"\n\n[deploi]\nimage = \"nginx\"\n";
+        fs::write(&path, content).unwrap();
+        let problems = validate(&path, false).unwrap();
+        assert!(problems.iter().any(|p| p.line == 3 && p.message.contains("Dockerfile")));
+        assert!(problems.iter().any(|p| p.line == 5 && p.message.contains("did you mean `deploy`?")));
+
+        // The remote manifest has no workspace to look for the Dockerfile.
+        let problems = validate(&path, true).unwrap();
+        assert!(!problems.iter().any(|p| p.message.contains("Dockerfile")));
+    }
+
+    #[test]
+    fn test_find_whole_key() {
+        let content = "export = 8080\n  port = 8080\n[[ports]]\nport=8080\n";
+        assert_eq!(find(content, "port", "8080", 1), Some((2, 3)));
+        assert_eq!(find(content, "port", "8080", 2), Some((4, 1)));
+        assert_eq!(find(content, "port", "80", 1), None);
+    }
+}
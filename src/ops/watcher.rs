@@ -12,81 +12,380 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::sync::mpsc::{RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
 
 use amp_client::client::Client;
 use amp_common::sync::{self, EventKinds, Synchronization};
-use ignore::gitignore::{Gitignore, GitignoreBuilder};
-use notify::event::RemoveKind;
-use notify::EventKind::Remove;
+use clap::ValueEnum;
+use notify::event::{ModifyKind, RemoveKind, RenameMode};
+use notify::EventKind::{Access, Create, Modify, Remove};
 use notify::RecursiveMode::Recursive;
-use notify::{Event, RecommendedWatcher, Watcher};
-use tracing::{debug, error, trace, warn};
+use notify::{Event, PollWatcher, RecommendedWatcher, Watcher};
+use tracing::{debug, error, info, trace, warn};
 
 use crate::errors::{Errors, Result};
+use crate::ops::ignorer::Matcher;
+use crate::state::STATE_DIR;
 use crate::utils;
 
-///  Watch file changes and sync the changed files.
-pub async fn watch(workspace: &Path, client: &Client, pid: &str, name: &str) -> Result<()> {
+/// The default quiet period to wait for more changes before syncing them together.
+pub const DEFAULT_DEBOUNCE: Duration = Duration::from_millis(300);
+/// The default interval to scan the workspace in polling mode.
+pub const DEFAULT_POLL_INTERVAL: Duration = Duration::from_secs(1);
+/// Sync the batch after this delay even if the changes keep coming.
+const MAX_BATCH_DELAY: Duration = Duration::from_secs(5);
+/// The file in the state directory touched by `amp sync` to sync the queued changes.
+const SYNC_REQUEST: &str = "sync.request";
+
+/// How the changes are detected and synced.
+#[derive(Clone, Copy, Debug, Default, PartialEq, ValueEnum)]
+pub enum Trigger {
+    /// Use the native file system events, falls back to polling if they are not available
+    #[default]
+    Notify,
+    /// Scan the workspace periodically, for network file systems, bind mounts and WSL
+    Polling,
+    /// Queue the changes until Enter is pressed or `amp sync` is run
+    Manual,
+}
+
+/// The settings of the watcher.
+#[derive(Debug, Clone)]
+pub struct Settings {
+    /// How the changes are detected and synced
+    pub trigger: Trigger,
+    /// How long to wait for more changes before syncing them together
+    pub debounce: Duration,
+    /// How often to scan the workspace in polling mode
+    pub poll_interval: Duration,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        Self { trigger: Trigger::default(), debounce: DEFAULT_DEBOUNCE, poll_interval: DEFAULT_POLL_INTERVAL }
+    }
+}
+
+/// The messages received by the watch loop.
+enum Message {
+    /// A file system event from the watcher
+    Event(notify::Result<Event>),
+    /// Sync the queued changes now
+    Flush,
+}
+
+/// Ask the dev session in the workspace to sync the queued changes.
+pub fn request_sync(workspace: &Path) -> Result<()> {
+    let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+    let path = workspace.join(STATE_DIR).join(SYNC_REQUEST);
+    fs::write(path, now.as_millis().to_string()).map_err(Errors::FailedSaveState)
+}
+
+///  Watch file changes and sync the changed files, which are not ignored by the matcher.
+pub async fn watch(
+    workspace: &Path,
+    client: &Client,
+    pid: &str,
+    name: &str,
+    matcher: Arc<Matcher>,
+    settings: &Settings,
+) -> Result<()> {
     let (tx, rx) = std::sync::mpsc::channel();
+    let _watcher = start(workspace, settings, tx.clone())?;
 
-    // We listen to the file changes giving Notify
-    // a function that will get called when events happen.
-    let config = notify::Config::default();
-    let mut watcher = RecommendedWatcher::new(tx, config).map_err(Errors::FailedCreateWatcher)?;
-    watcher.watch(workspace, Recursive).map_err(Errors::FailedWatchDirectory)?;
+    let manual = settings.trigger == Trigger::Manual;
+    if manual {
+        // Every line from the terminal syncs the queued changes.
+        std::thread::spawn(move || {
+            for _ in std::io::stdin().lines() {
+                if tx.send(Message::Flush).is_err() {
+                    break;
+                }
+            }
+        });
+    }
 
-    let mut builder = GitignoreBuilder::new(workspace);
-    builder.add(".gitignore");
-    let matcher = builder.build().unwrap();
+    // Collect the changes until they are quiet for the debounce window,
+    // and sync them in one go.
+    let mut queue = Queue::new(workspace, &matcher, manual);
 
-    for event in rx {
-        if let Err(err) = event {
-            error!("Got a notify error: {err:?}");
-            continue;
+    loop {
+        let received = match queue.batch.is_empty() || manual {
+            true => rx.recv().map_err(|_| RecvTimeoutError::Disconnected),
+            false => rx.recv_timeout(settings.debounce),
+        };
+        if let Err(RecvTimeoutError::Disconnected) = received {
+            break;
         }
-        let event = event.unwrap();
-        if is_ignored(&matcher, workspace, &event.paths)? {
+        if !queue.receive(received, &matcher) {
             continue;
         }
 
-        handle(client, pid, name, workspace, event).await?;
+        // A failed batch is reported, the later changes are still synced.
+        if let Err(err) = flush(client, pid, name, workspace, &matcher, queue.take()).await {
+            error!("Failed to sync the changes: {:?}", err);
+        }
     }
 
     Ok(())
 }
 
-async fn handle(client: &Client, pid: &str, name: &str, base: &Path, event: Event) -> Result<()> {
-    trace!("Changed: {:?}", event);
+/// Start watching the workspace, the native events fall back to polling if
+/// they can not be initialised, e.g. the inotify limits are reached.
+fn start(workspace: &Path, settings: &Settings, tx: Sender<Message>) -> Result<Box<dyn Watcher + Send>> {
+    if settings.trigger != Trigger::Polling {
+        let watcher = RecommendedWatcher::new(handler(tx.clone()), notify::Config::default());
+        match watcher.and_then(|mut w| w.watch(workspace, Recursive).map(|_| w)) {
+            Ok(watcher) => return Ok(Box::new(watcher)),
+            Err(err) => warn!("Failed to watch with notify: {}, falling back to polling", err),
+        }
+    }
+
+    debug!("Polling the workspace every {:?}", settings.poll_interval);
+    let config = notify::Config::default().with_poll_interval(settings.poll_interval);
+    let mut watcher = PollWatcher::new(handler(tx), config).map_err(Errors::FailedCreateWatcher)?;
+    watcher.watch(workspace, Recursive).map_err(Errors::FailedWatchDirectory)?;
+
+    Ok(Box::new(watcher))
+}
+
+fn handler(tx: Sender<Message>) -> impl FnMut(notify::Result<Event>) + Send + 'static {
+    move |event| {
+        let _ = tx.send(Message::Event(event));
+    }
+}
+
+/// The changes queued by the watch loop, until they are quiet, too old, or requested to sync.
+struct Queue {
+    batch: Batch,
+    directories: Directories,
+    /// When the first change of the batch was queued
+    started: Instant,
+    manual: bool,
+    /// The file touched by `amp sync`
+    request: PathBuf,
+}
+
+impl Queue {
+    fn new(workspace: &Path, matcher: &Arc<Matcher>, manual: bool) -> Self {
+        Self {
+            batch: Batch::default(),
+            directories: Directories::scan(workspace, matcher),
+            started: Instant::now(),
+            manual,
+            request: workspace.join(STATE_DIR).join(SYNC_REQUEST),
+        }
+    }
+
+    /// Queue the changes of the received message, returns whether the batch
+    /// should be synced now.
+    fn receive(&mut self, received: std::result::Result<Message, RecvTimeoutError>, matcher: &Matcher) -> bool {
+        match received {
+            Ok(Message::Event(Ok(event))) if event.paths.contains(&self.request) => {
+                // Only a written request syncs the changes, and only in manual mode.
+                if !self.manual || matches!(event.kind, Remove(_) | Access(_)) {
+                    return false;
+                }
+            }
+            Ok(Message::Event(Ok(event))) => {
+                trace!("Changed: {:?}", event);
+                let queued = self.batch.is_empty();
+                if queued {
+                    self.started = Instant::now();
+                }
+                event.paths.iter().for_each(|path| matcher.reload(path));
+                self.batch.add(&event, &mut self.directories, |path, is_dir| matcher.is_ignored(path, is_dir));
+                if self.manual && queued && !self.batch.is_empty() {
+                    info!("Changes are queued, press Enter or run `amp sync` to sync them");
+                }
+                if self.manual || self.started.elapsed() < MAX_BATCH_DELAY {
+                    return false;
+                }
+            }
+            Ok(Message::Event(Err(err))) => {
+                error!("Got a notify error: {err:?}");
+                return false;
+            }
+            Ok(Message::Flush) | Err(_) => {}
+        }
 
-    let kind = EventKinds::from(event.kind);
-    if kind == EventKinds::Rename || kind == EventKinds::Other {
-        warn!("Not supported event: {:?}", event);
-        return Ok(());
+        !self.batch.is_empty()
     }
 
-    let mut paths: Vec<(PathBuf, PathBuf)> = vec![];
-    for path in event.paths {
-        paths.push(utils::strip(base, &path)?);
+    /// Take the batch to sync, the renames still waiting for their new paths are
+    /// kept for the next batch, unless they waited too long to be paired.
+    fn take(&mut self) -> Batch {
+        let mut renames = std::mem::take(&mut self.batch.renames);
+        renames.retain(|_, (_, seen)| seen.elapsed() < MAX_BATCH_DELAY);
+        std::mem::replace(&mut self.batch, Batch { renames, ..Default::default() })
     }
+}
 
-    let mut req = Synchronization { kind: kind.clone(), paths: vec![], attributes: None, payload: None };
+/// The changes collected in a debounce window, collapsed by path.
+#[derive(Debug, Default)]
+struct Batch {
+    changes: BTreeMap<PathBuf, Change>,
+    /// The old paths of the renames waiting for their new paths, by the tracker,
+    /// with the time they are seen.
+    renames: HashMap<usize, (PathBuf, Instant)>,
+}
 
-    // Because the file or directory was removed yet, we can't get the file type.
-    // so we determine the file type by original event kind.
-    if kind == EventKinds::Remove {
-        let is_dir = event.kind == Remove(RemoveKind::Folder);
-        req.paths = paths.iter().map(|(_, b)| format_path(b, is_dir)).collect();
-    } else {
-        req.paths = paths.iter().map(|(a, b)| format_path(b, a.is_dir())).collect();
+#[derive(Debug, Clone, Copy)]
+struct Change {
+    /// The path didn't exist before the batch
+    created: bool,
+    /// The path is, or was before it's removed, a directory
+    is_dir: bool,
+}
+
+impl Batch {
+    fn is_empty(&self) -> bool {
+        self.changes.is_empty()
     }
 
-    if kind == EventKinds::Modify {
-        req.payload = Some(utils::archive(&paths)?);
+    /// Add the paths of the event, a rename is treated as the removal of
+    /// the old path and the creation of the new one. The old path of a renamed
+    /// directory is paired with the new one, to be removed as a directory.
+    fn add(&mut self, event: &Event, directories: &mut Directories, ignored: impl Fn(&Path, bool) -> bool) {
+        let renamed = match (event.kind, event.paths.as_slice()) {
+            (Modify(ModifyKind::Name(RenameMode::Both)), [from, to]) => Some((from.clone(), to)),
+            (Modify(ModifyKind::Name(RenameMode::From)), [from]) => {
+                if let Some(tracker) = event.tracker() {
+                    self.renames.insert(tracker, (from.clone(), Instant::now()));
+                }
+                None
+            }
+            (Modify(ModifyKind::Name(RenameMode::To)), [to]) => {
+                event.tracker().and_then(|tracker| self.renames.remove(&tracker)).map(|(from, _)| (from, to))
+            }
+            _ => None,
+        };
+
+        for (index, path) in event.paths.iter().enumerate() {
+            // Whether it's a directory is recorded now, the path might be gone when the batch is synced.
+            let is_dir = directories.stat(path) || event.kind == Remove(RemoveKind::Folder);
+            if ignored(path, is_dir) {
+                continue;
+            }
+
+            let created = match event.kind {
+                Create(_) | Modify(ModifyKind::Name(RenameMode::To)) => true,
+                Modify(ModifyKind::Name(RenameMode::Both)) => index == 1,
+                _ => false,
+            };
+            self.changes.entry(path.clone()).or_insert(Change { created, is_dir }).is_dir |= is_dir;
+        }
+
+        // The old path synced with an earlier batch is removed again, as a directory.
+        if let Some((from, to)) = renamed.filter(|(_, to)| to.is_dir()) {
+            self.changes.entry(from).or_insert(Change { created: false, is_dir: true }).is_dir = true;
+        }
     }
 
-    debug!("The sync request is: {:?}", req);
-    client.actors().sync(pid, name, req).await.map_err(Errors::ClientError)?;
+    /// Resolve the final state of the paths by whether they exist now, returns the
+    /// modified paths and the removed ones. The paths created and removed within the
+    /// batch, and the ones under a removed directory, are dropped.
+    fn resolve(self) -> (Vec<PathBuf>, Vec<(PathBuf, bool)>) {
+        let mut modified = vec![];
+        let mut removed: Vec<(PathBuf, bool)> = vec![];
+
+        for (path, change) in self.changes {
+            if path.exists() {
+                modified.push(path);
+            } else if !change.created && !removed.iter().any(|(dir, _)| path.starts_with(dir)) {
+                removed.push((path, change.is_dir));
+            }
+        }
+
+        (modified, removed)
+    }
+}
+
+/// The directories of the workspace, to tell whether a removed path was a directory.
+#[derive(Debug, Default)]
+struct Directories(HashSet<PathBuf>);
+
+impl Directories {
+    fn scan(workspace: &Path, matcher: &Arc<Matcher>) -> Self {
+        let entries = utils::walk(workspace, matcher).unwrap_or_else(|err| {
+            warn!("Failed to scan the directories of the workspace: {:?}", err);
+            vec![]
+        });
+        Self(entries.into_iter().map(|e| e.into_path()).filter(|p| p.is_dir()).collect())
+    }
+
+    /// Stat the path and remember it if it's a directory, returns whether it is,
+    /// or was before it's removed, a directory.
+    fn stat(&mut self, path: &Path) -> bool {
+        match fs::symlink_metadata(path) {
+            Ok(metadata) if metadata.is_dir() => {
+                self.0.insert(path.to_path_buf());
+                true
+            }
+            Ok(_) => {
+                self.0.remove(path);
+                false
+            }
+            Err(_) => {
+                let was = self.0.contains(path);
+                self.0.retain(|dir| !dir.starts_with(path));
+                was
+            }
+        }
+    }
+}
+
+/// Sync the batch with at most one remove request and one archive of the modified files.
+async fn flush(
+    client: &Client,
+    pid: &str,
+    name: &str,
+    base: &Path,
+    matcher: &Arc<Matcher>,
+    batch: Batch,
+) -> Result<()> {
+    let (modified, removed) = batch.resolve();
+
+    if !removed.is_empty() {
+        let mut paths = vec![];
+        for (path, is_dir) in &removed {
+            let (_, relative) = utils::strip(base, path)?;
+            paths.push(format_path(&relative, *is_dir));
+        }
+        let req = Synchronization { kind: EventKinds::Remove, paths, attributes: None, payload: None };
+        debug!("The sync request is: {:?}", req.paths);
+        client.actors().sync(pid, name, req).await.map_err(Errors::ClientError)?;
+    }
+
+    if !modified.is_empty() {
+        // The directories moved into the workspace come with all their contents.
+        let mut files = BTreeSet::new();
+        let mut paths = vec![];
+        for path in &modified {
+            if path.is_dir() {
+                files.extend(utils::walk(path, matcher)?.into_iter().map(|e| e.into_path()).filter(|p| p.is_file()));
+            } else {
+                files.insert(path.clone());
+            }
+            let (_, relative) = utils::strip(base, path)?;
+            paths.push(format_path(&relative, path.is_dir()));
+        }
+
+        let mut archived = vec![];
+        for file in &files {
+            archived.push(utils::strip(base, file)?);
+        }
+        let payload = Some(utils::archive(&archived)?);
+        let req = Synchronization { kind: EventKinds::Modify, paths, attributes: None, payload };
+
+        debug!("The sync request is: {:?}", req.paths);
+        client.actors().sync(pid, name, req).await.map_err(Errors::ClientError)?;
+    }
 
     Ok(())
 }
@@ -99,14 +398,159 @@ fn format_path(path: &Path, is_dir: bool) -> sync::Path {
     }
 }
 
-fn is_ignored(matcher: &Gitignore, root: &Path, paths: &Vec<PathBuf>) -> Result<bool> {
-    for path in paths {
-        let name = path.strip_prefix(root).map_err(Errors::FailedStripPrefix)?;
-        if matcher.matched(name, false).is_ignore() {
-            debug!("The file is ignored: {:?}", name);
-            return Ok(true);
-        }
+#[cfg(test)]
+mod tests {
+    use notify::event::{CreateKind, DataChange};
+
+    use super::*;
+
+    #[test]
+    fn test_collapse_batch() {
+        let dir = utils::workspace(&[("main.rs", "fn main() {}"), (".gitignore", "*.tmp")]);
+        let root = dir.path();
+
+        let matcher = Matcher::new(root, &Default::default()).unwrap();
+        let ignored = |path: &Path, is_dir: bool| matcher.is_ignored(path, is_dir);
+
+        let mut batch = Batch::default();
+        let mut directories = Directories::default();
+        // Saving via a temporary file only syncs the real file.
+        let rename = Event::new(Modify(ModifyKind::Name(RenameMode::Both)));
+        batch.add(&rename.add_path(root.join("main.rs.tmp")).add_path(root.join("main.rs")), &mut directories, ignored);
+        batch.add(
+            &Event::new(Modify(ModifyKind::Data(DataChange::Any))).add_path(root.join("main.rs")),
+            &mut directories,
+            ignored,
+        );
+        // A file created and removed within the batch is never synced.
+        batch.add(&Event::new(Create(CreateKind::File)).add_path(root.join("scratch")), &mut directories, ignored);
+        batch.add(&Event::new(Remove(RemoveKind::File)).add_path(root.join("scratch")), &mut directories, ignored);
+        // The files under a removed directory are removed with it.
+        batch.add(&Event::new(Remove(RemoveKind::Folder)).add_path(root.join("old")), &mut directories, ignored);
+        batch.add(&Event::new(Remove(RemoveKind::File)).add_path(root.join("old/lib.rs")), &mut directories, ignored);
+
+        let (modified, removed) = batch.resolve();
+        assert_eq!(modified, vec![root.join("main.rs")]);
+        assert_eq!(removed, vec![(root.join("old"), true)]);
     }
 
-    Ok(false)
+    #[test]
+    fn test_pair_renames() {
+        let dir = utils::workspace(&[("new/lib.rs", ""), ("main.rs", "")]);
+        let root = dir.path();
+
+        let mut batch = Batch::default();
+        let mut directories = Directories::default();
+        // The directory renamed in one event is removed as a directory.
+        let rename = Event::new(Modify(ModifyKind::Name(RenameMode::Both)));
+        batch.add(&rename.add_path(root.join("old")).add_path(root.join("new")), &mut directories, |_, _| false);
+        // The two sides of a rename are paired by the tracker.
+        let from = Event::new(Modify(ModifyKind::Name(RenameMode::From))).set_tracker(1);
+        batch.add(&from.add_path(root.join("src")), &mut directories, |_, _| false);
+        let to = Event::new(Modify(ModifyKind::Name(RenameMode::To))).set_tracker(1);
+        batch.add(&to.add_path(root.join("new")), &mut directories, |_, _| false);
+        // The renamed file stays a file.
+        let from = Event::new(Modify(ModifyKind::Name(RenameMode::From))).set_tracker(2);
+        batch.add(&from.add_path(root.join("main.rs.bak")), &mut directories, |_, _| false);
+        let to = Event::new(Modify(ModifyKind::Name(RenameMode::To))).set_tracker(2);
+        batch.add(&to.add_path(root.join("main.rs")), &mut directories, |_, _| false);
+
+        let (modified, removed) = batch.resolve();
+        assert_eq!(modified, vec![root.join("main.rs"), root.join("new")]);
+        assert_eq!(
+            removed,
+            vec![(root.join("main.rs.bak"), false), (root.join("old"), true), (root.join("src"), true)]
+        );
+    }
+
+    #[test]
+    fn test_remove_moved_out_directory() {
+        let dir = utils::workspace(&[("src/lib.rs", "")]);
+        let root = dir.path();
+        let matcher = Arc::new(Matcher::new(root, &Default::default()).unwrap());
+
+        let mut batch = Batch::default();
+        let mut directories = Directories::scan(root, &matcher);
+        // The directory moved out of the workspace has no rename-to event to pair with.
+        let outside = tempfile::tempdir().unwrap();
+        fs::rename(root.join("src"), outside.path().join("src")).unwrap();
+        let from = Event::new(Modify(ModifyKind::Name(RenameMode::From))).set_tracker(1);
+        batch.add(&from.add_path(root.join("src")), &mut directories, |_, _| false);
+
+        let (_, removed) = batch.resolve();
+        assert_eq!(removed, vec![(root.join("src"), true)]);
+    }
+
+    #[test]
+    fn test_carry_renames() {
+        let dir = utils::workspace(&[("new/lib.rs", "")]);
+        let root = dir.path();
+        let matcher = Arc::new(Matcher::new(root, &Default::default()).unwrap());
+        let from = Event::new(Modify(ModifyKind::Name(RenameMode::From))).set_tracker(1).add_path(root.join("old"));
+        let to = Event::new(Modify(ModifyKind::Name(RenameMode::To))).set_tracker(1).add_path(root.join("new"));
+
+        // The old path is synced with the first batch, the new one pairs with it in the next.
+        let mut queue = Queue::new(root, &matcher, true);
+        assert!(!queue.receive(Ok(Message::Event(Ok(from))), &matcher));
+        assert!(queue.receive(Ok(Message::Flush), &matcher));
+        assert_eq!(queue.take().resolve().1, vec![(root.join("old"), false)]);
+        assert!(!queue.receive(Ok(Message::Event(Ok(to))), &matcher));
+
+        let (modified, removed) = queue.take().resolve();
+        assert_eq!(modified, vec![root.join("new")]);
+        assert_eq!(removed, vec![(root.join("old"), true)]);
+        assert!(queue.batch.renames.is_empty());
+    }
+
+    #[test]
+    fn test_request_sync() {
+        let dir = utils::workspace(&[(".amp/session.toml", "")]);
+        request_sync(dir.path()).unwrap();
+
+        let request = fs::read_to_string(dir.path().join(STATE_DIR).join(SYNC_REQUEST)).unwrap();
+        assert!(request.parse::<u128>().is_ok());
+    }
+
+    #[test]
+    fn test_queue_manual() {
+        let dir = utils::workspace(&[("main.rs", ""), (".amp/session.toml", "")]);
+        let root = dir.path();
+        let matcher = Arc::new(Matcher::new(root, &Default::default()).unwrap());
+        let changed = || Message::Event(Ok(Event::new(Create(CreateKind::File)).add_path(root.join("main.rs"))));
+        let request = |kind| Message::Event(Ok(Event::new(kind).add_path(root.join(STATE_DIR).join(SYNC_REQUEST))));
+
+        // The changes are queued until Enter is pressed.
+        let mut queue = Queue::new(root, &matcher, true);
+        assert!(!queue.receive(Ok(changed()), &matcher));
+        assert!(!queue.batch.is_empty());
+        assert!(queue.receive(Ok(Message::Flush), &matcher));
+
+        // Or until `amp sync` writes the request, removing it doesn't sync.
+        let mut queue = Queue::new(root, &matcher, true);
+        assert!(!queue.receive(Ok(request(Create(CreateKind::File))), &matcher));
+        assert!(!queue.receive(Ok(changed()), &matcher));
+        assert!(!queue.receive(Ok(request(Remove(RemoveKind::File))), &matcher));
+        assert!(queue.receive(Ok(request(Modify(ModifyKind::Data(DataChange::Any)))), &matcher));
+    }
+
+    #[test]
+    fn test_queue_debounce() {
+        let dir = utils::workspace(&[("main.rs", "")]);
+        let root = dir.path();
+        let matcher = Arc::new(Matcher::new(root, &Default::default()).unwrap());
+        let changed = Message::Event(Ok(Event::new(Create(CreateKind::File)).add_path(root.join("main.rs"))));
+        let request = Event::new(Create(CreateKind::File)).add_path(root.join(STATE_DIR).join(SYNC_REQUEST));
+
+        // The changes from polling or notify are synced when they are quiet.
+        let mut queue = Queue::new(root, &matcher, false);
+        assert!(!queue.receive(Err(RecvTimeoutError::Timeout), &matcher));
+        assert!(!queue.receive(Ok(changed), &matcher));
+        assert!(!queue.receive(Ok(Message::Event(Ok(request))), &matcher));
+        assert!(queue.receive(Err(RecvTimeoutError::Timeout), &matcher));
+
+        // Or when the batch is too old, even if the changes keep coming.
+        queue.started -= MAX_BATCH_DELAY;
+        let changed = Message::Event(Ok(Event::new(Modify(ModifyKind::Any)).add_path(root.join("main.rs"))));
+        assert!(queue.receive(Ok(changed), &matcher));
+    }
 }
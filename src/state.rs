@@ -0,0 +1,114 @@
+// Copyright (c) The Amphitheatre Authors. All rights reserved.
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//      https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+use tracing::debug;
+
+use crate::errors::{Errors, Result};
+
+/// The directory in the workspace where amp keeps its local state.
+pub const STATE_DIR: &str = ".amp";
+const STATE_FILE: &str = "session.toml";
+
+/// State is the persisted part of a dev session, written into the workspace
+/// so the playbook can be found again after the process crashed or was killed.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct State {
+    /// The server address of the context the playbook was created on
+    pub server: String,
+    /// The ID of the playbook
+    pub playbook: String,
+    /// The name of the lead character
+    pub character: String,
+    /// Whether the changes are queued until `amp sync` is run
+    #[serde(default)]
+    pub manual: bool,
+}
+
+impl State {
+    pub fn new(server: &str, playbook: &str, character: &str) -> Self {
+        Self {
+            server: server.to_string(),
+            playbook: playbook.to_string(),
+            character: character.to_string(),
+            manual: false,
+        }
+    }
+
+    /// The path of the state file in the given workspace.
+    pub fn path(workspace: &Path) -> PathBuf {
+        workspace.join(STATE_DIR).join(STATE_FILE)
+    }
+
+    /// Load the state from the given workspace, returns None if there is no state file.
+    pub fn load(workspace: &Path) -> Result<Option<State>> {
+        let path = Self::path(workspace);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let content = fs::read_to_string(&path).map_err(Errors::FailedLoadState)?;
+        let state = toml::from_str(&content).map_err(Errors::TomlDeserializeError)?;
+        debug!("Loaded session state from {:?}: {:?}", path, state);
+
+        Ok(Some(state))
+    }
+
+    /// Save the state into the given workspace.
+    pub fn save(&self, workspace: &Path) -> Result<()> {
+        let path = Self::path(workspace);
+        fs::create_dir_all(workspace.join(STATE_DIR)).map_err(Errors::FailedSaveState)?;
+
+        let content = toml::to_string(self).map_err(Errors::TomlSerializeError)?;
+        fs::write(&path, content).map_err(Errors::FailedSaveState)?;
+        debug!("Saved session state to {:?}", path);
+
+        Ok(())
+    }
+
+    /// Remove the state file from the given workspace, if any.
+    pub fn remove(workspace: &Path) -> Result<()> {
+        let path = Self::path(workspace);
+        if path.exists() {
+            fs::remove_file(&path).map_err(Errors::FailedSaveState)?;
+            debug!("Removed session state {:?}", path);
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::utils;
+
+    #[test]
+    fn test_save_and_load_state() {
+        let dir = utils::workspace(&[]);
+        let workspace = dir.path().to_path_buf();
+
+        assert_eq!(State::load(&workspace).unwrap(), None);
+
+        let state = State::new("http://localhost:8170", "1a2b3c", "amp-example-go");
+        state.save(&workspace).unwrap();
+        assert_eq!(State::load(&workspace).unwrap(), Some(state));
+
+        State::remove(&workspace).unwrap();
+        assert_eq!(State::load(&workspace).unwrap(), None);
+    }
+}
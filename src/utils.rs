@@ -12,23 +12,39 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
+use std::io::IsTerminal;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
 use amp_client::actors::Actors;
 use amp_common::sync::{EventKinds, Synchronization};
-use ignore::WalkBuilder;
+use ignore::{DirEntry, WalkBuilder};
+use reqwest::header::{CONNECTION, UPGRADE};
+use reqwest::{RequestBuilder, StatusCode, Upgraded};
 use tar::Builder;
 use tracing::debug;
 
 use crate::errors::{Errors, Result};
+use crate::ops::ignorer::Matcher;
+
+/// Whether to skip the yes/no confirmations, without the flag the user is
+/// only asked when the input is a terminal.
+pub fn assume_yes(flag: Option<bool>) -> bool {
+    flag.unwrap_or_else(|| !std::io::stdin().is_terminal())
+}
 
 /// Upload the given directory to the server.
-pub async fn upload(client: &Actors<'_>, pid: &str, name: &str, workspace: &Path) -> Result<()> {
+pub async fn upload(
+    client: &Actors<'_>,
+    pid: &str,
+    name: &str,
+    workspace: &Path,
+    matcher: &Arc<Matcher>,
+) -> Result<()> {
     let mut paths: Vec<(PathBuf, PathBuf)> = vec![];
 
     let base = workspace;
-    for entry in WalkBuilder::new(workspace).build() {
-        let entry = entry.map_err(Errors::WalkError)?;
+    for entry in walk(workspace, matcher)? {
         let path = entry.path();
 
         if path.is_dir() {
@@ -45,6 +61,22 @@ pub async fn upload(client: &Actors<'_>, pid: &str, name: &str, workspace: &Path
     Ok(())
 }
 
+/// Walk the given directory with the ignore rules, returns both files and directories.
+pub fn walk(dir: &Path, matcher: &Arc<Matcher>) -> Result<Vec<DirEntry>> {
+    let matcher = matcher.clone();
+    let walker = WalkBuilder::new(dir)
+        .standard_filters(false)
+        .filter_entry(move |entry| !matcher.is_ignored(entry.path(), entry.file_type().is_some_and(|t| t.is_dir())))
+        .build();
+
+    let mut entries = vec![];
+    for entry in walker {
+        entries.push(entry.map_err(Errors::WalkError)?);
+    }
+
+    Ok(entries)
+}
+
 /// Archive the given directory into a tarball and return the bytes.
 pub fn archive(paths: &Vec<(PathBuf, PathBuf)>) -> Result<Vec<u8>> {
     debug!("The given path for archive is {:?}", paths);
@@ -62,3 +94,31 @@ pub fn strip(base: &Path, path: &Path) -> Result<(PathBuf, PathBuf)> {
     debug!("the full path and striped path is: {:?}, {:?}", path, striped_path);
     Ok((path.to_path_buf(), striped_path.to_path_buf()))
 }
+
+/// Send the request and upgrade the connection into a raw bidirectional stream.
+pub async fn upgrade(req: RequestBuilder, token: &Option<String>, protocol: &str) -> Result<Upgraded> {
+    let mut req = req.header(CONNECTION, "upgrade").header(UPGRADE, protocol);
+    if let Some(token) = token {
+        req = req.bearer_auth(token);
+    }
+
+    let res = req.send().await.map_err(|e| Errors::FailedOpenTunnel(e.to_string()))?;
+    if res.status() != StatusCode::SWITCHING_PROTOCOLS {
+        return Err(Errors::FailedOpenTunnel(format!("unexpected status {}", res.status())));
+    }
+
+    res.upgrade().await.map_err(|e| Errors::FailedOpenTunnel(e.to_string()))
+}
+
+/// Create a temporary workspace with the given files for the tests,
+/// it is removed when the returned directory is dropped.
+#[cfg(test)]
+pub fn workspace(files: &[(&str, &str)]) -> tempfile::TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    for (name, content) in files {
+        let path = dir.path().join(name);
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(path, content).unwrap();
+    }
+    dir
+}